@@ -16,7 +16,7 @@ use crossterm::{
 };
 use std::borrow::Cow;
 use filez::util::{wrap_text, RectExt, Split2D};
-use filez::widgets::SliderBuilder;
+use filez::widgets::Slider;
 
 fn main() -> io::Result<()> {
     // setup terminal
@@ -123,7 +123,7 @@ fn main() -> io::Result<()> {
                 area_compare,
             );
 
-            let slider = SliderBuilder::new(offset_y, contents.len()).make(area_vslider, Direction::Vertical).slider_color(Color::Red);
+            let slider = Slider::new(offset_y, contents.len()).direction(Direction::Vertical).slider_color(Color::Red);
             f.render_widget(
                 // Gauge::default()
                 // .block(Block::default().borders(Borders::ALL).title("Progress"))
@@ -142,9 +142,9 @@ fn main() -> io::Result<()> {
                 area_vslider
             );
 
-            let slider = SliderBuilder::new(offset_x, text.width()).make(area_hslider, Direction::Horizontal).slider_color(Color::Green);
+            let slider = Slider::new(offset_x, text.width()).direction(Direction::Horizontal).slider_color(Color::Green);
             f.render_widget(
-                Paragraph::new(Text::from(format!("{} {}: {:?}", offset_x, offset_y, slider.size())))
+                Paragraph::new(Text::from(format!("{} {}: {:?}", offset_x, offset_y, slider.positon(area_hslider))))
                 .block(Block::default().title("notify").borders(Borders::ALL))
                 .style(Style::default().fg(Color::White).bg(Color::Black))
                 .alignment(Alignment::Center)
@@ -1,18 +1,42 @@
+use filez::config::Config;
+use filez::theme::Theme;
 use filez::ui::LogRecord;
 use std::io;
+use std::path::Path;
 use clap::{arg, command};
 
 fn main() -> io::Result<()> {
     let matches = command!()
-        .arg(arg!([LEFT]))
-        .arg(arg!([RIGHT]))
+        .arg(arg!([LEFT] "local path or [user@]host:path"))
+        .arg(arg!([RIGHT] "local path or [user@]host:path"))
+        .arg(arg!(-c --config <FILE> "config file instead of $XDG_CONFIG_HOME/filez/config.toml").required(false))
+        .arg(arg!(--"dump-config" "print the effective config and exit"))
         .get_matches();
 
-    let left = matches.value_of("LEFT").expect("left path not specify");
-    let right = matches.value_of("RIGHT").expect("right path not specify");
+    let config = match Config::load(matches.value_of("config").map(Path::new)) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("filez: {}", e);
+            std::process::exit(2);
+        },
+    };
+    if matches.is_present("dump-config") {
+        print!("{}", config.dump());
+        return Ok(());
+    }
+    let theme = match Theme::load(&config.theme) {
+        Ok(theme) => theme,
+        Err(e) => {
+            eprintln!("filez: {}", e);
+            std::process::exit(2);
+        },
+    };
+
+    let left = matches.value_of("LEFT").unwrap_or(&config.panels.left);
+    let right = matches.value_of("RIGHT").unwrap_or(&config.panels.right);
 
     let (tx, rx) = std::sync::mpsc::sync_channel(1024);
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("debug"))
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(&config.logging.level))
         .format(move |buf, record| {
             let _ = tx
                 .try_send(LogRecord {
@@ -30,5 +54,5 @@ fn main() -> io::Result<()> {
     log::debug!("hello from main");
     log::error!("hello from main");
 
-    filez::ui::run(rx, left, right)
+    filez::ui::run(rx, &config, &theme, left, right)
 }
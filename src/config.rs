@@ -0,0 +1,277 @@
+use crate::theme::ColorDepth;
+use crate::ui::explorer::{Columns, SortKey};
+use crate::ui::keymap::{Action, Context, Keymap, Keys};
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Settings read from `config.toml`, every key may be left out for its default.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub panels: PanelsConfig,
+    pub theme: ThemeConfig,
+    pub keys: KeysConfig,
+    pub logging: LoggingConfig,
+    pub layout: LayoutConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PanelsConfig {
+    /// Startup locations when none is given on the command line, local paths or `[user@]host:path`.
+    pub left: String,
+    pub right: String,
+
+    pub show_hidden: bool,
+    #[serde(with = "as_str")]
+    pub sort: SortKey,
+    pub sort_reverse: bool,
+    pub dirs_first: bool,
+    pub auto_size: bool,
+    #[serde(with = "as_str")]
+    pub left_columns: Columns,
+    #[serde(with = "as_str")]
+    pub right_columns: Columns,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThemeConfig {
+    /// One of the built-in themes: dark, light, solarized or high-contrast.
+    pub name: String,
+    /// A theme file whose styles replace the ones of `name`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<PathBuf>,
+    /// Colors of the terminal, `auto` guesses from `$COLORTERM` and `$TERM`.
+    #[serde(with = "as_str")]
+    pub colors: ColorDepth,
+    /// Color names like `ls` does with `$LS_COLORS`.
+    pub ls_colors: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeysConfig {
+    /// How long a key sequence waits for the next key.
+    pub leader_timeout_ms: u64,
+
+    /// Actions bound to other keys than the default ones, like `top = ["g g", "Home"]`.
+    /// The keys are taken from the action they had, an empty list unbinds the action.
+    pub normal: BTreeMap<Action, Vec<Keys>>,
+    /// Keys of the focused info panel, the normal ones apply when not bound here.
+    pub info: BTreeMap<Action, Vec<Keys>>,
+    /// Keys of the focused preview, the normal ones apply when not bound here.
+    pub preview: BTreeMap<Action, Vec<Keys>>,
+    /// Keys while typing a search, single chords only.
+    #[serde(deserialize_with = "single_chords::deserialize")]
+    pub search: BTreeMap<Action, Vec<Keys>>,
+    /// Keys of popups, single chords only.
+    #[serde(deserialize_with = "single_chords::deserialize")]
+    pub popup: BTreeMap<Action, Vec<Keys>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    /// `env_logger` filter, `RUST_LOG` wins over it.
+    pub level: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LayoutConfig {
+    /// Rows of the info panel, it is hidden on terminals too small for it.
+    pub info_height: u16,
+    /// Time between two frames.
+    pub frame_ms: u64,
+}
+
+impl Default for PanelsConfig {
+    fn default() -> Self {
+        Self {
+            left: ".".to_owned(),
+            right: ".".to_owned(),
+
+            show_hidden: false,
+            sort: SortKey::Natural,
+            sort_reverse: false,
+            dirs_first: true,
+            auto_size: false,
+            left_columns: Columns::default(),
+            right_columns: Columns::default(),
+        }
+    }
+}
+
+impl Default for ThemeConfig {
+    fn default() -> Self {
+        Self { name: "dark".to_owned(), file: None, colors: ColorDepth::Auto, ls_colors: true }
+    }
+}
+
+impl Default for KeysConfig {
+    fn default() -> Self {
+        Self {
+            leader_timeout_ms: 5000,
+            normal: BTreeMap::new(),
+            info: BTreeMap::new(),
+            preview: BTreeMap::new(),
+            search: BTreeMap::new(),
+            popup: BTreeMap::new(),
+        }
+    }
+}
+
+impl KeysConfig {
+    /// The default bindings with these ones applied, an action bound by default but not anymore
+    /// is left with no keys.
+    pub fn effective(&self) -> Self {
+        let keymap = Keymap::new(self);
+        let defaults = Keymap::default();
+        let table = |context| {
+            let mut table: BTreeMap<Action, Vec<Keys>> = defaults.bindings(context).into_iter()
+                .map(|(action, _)| (action, vec![]))
+                .collect();
+            for (action, keys) in keymap.bindings(context) {
+                table.insert(action, keys.into_iter().cloned().collect());
+            }
+            table
+        };
+        Self {
+            leader_timeout_ms: self.leader_timeout_ms,
+            normal: table(Context::Normal),
+            info: table(Context::Info),
+            preview: table(Context::Preview),
+            search: table(Context::Search),
+            popup: table(Context::Popup),
+        }
+    }
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self { level: "debug".to_owned() }
+    }
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        Self { info_height: 20, frame_ms: 15 }
+    }
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/filez/config.toml`, or under `~/.config` without it.
+    pub fn default_path() -> Option<PathBuf> {
+        let base = match std::env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+        Some(base.join("filez").join("config.toml"))
+    }
+
+    /// Read `path`, or the default path where a missing file means the defaults.
+    pub fn load(path: Option<&Path>) -> Result<Self, String> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match Self::default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Self::default()),
+            },
+        };
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if !required && e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        };
+        // the error tells the line and column
+        toml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// The effective config, as it would be written in the file, with every key binding.
+    pub fn dump(&self) -> String {
+        let config = Self { keys: self.keys.effective(), ..self.clone() };
+        toml::to_string_pretty(&config).expect("config is always serializable")
+    }
+}
+
+/// Values written as their `Display` and read back with `FromStr`.
+mod as_str {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use std::fmt::Display;
+    use std::str::FromStr;
+
+    pub fn serialize<T: Display, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(value)
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: FromStr<Err = String>,
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?.parse().map_err(D::Error::custom)
+    }
+}
+
+/// Bindings of the tables without key sequences, a sequence is an error at its line.
+mod single_chords {
+    use super::{Action, Keys};
+    use serde::{de::Error, Deserialize, Deserializer};
+    use std::collections::BTreeMap;
+
+    struct Single(Keys);
+
+    impl<'de> Deserialize<'de> for Single {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let keys = Keys::deserialize(deserializer)?;
+            if keys.0.len() > 1 {
+                return Err(D::Error::custom(format!("`{}` is a sequence, only single chords are bound here", keys)));
+            }
+            Ok(Self(keys))
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BTreeMap<Action, Vec<Keys>>, D::Error> {
+        let bindings = BTreeMap::<Action, Vec<Single>>::deserialize(deserializer)?;
+        Ok(bindings.into_iter().map(|(action, keys)| (action, keys.into_iter().map(|k| k.0).collect())).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dump_has_every_binding() {
+        let text = "[keys.normal]\ntop = [\"g g\", \"Home\"]\nquit = []\n";
+        let config: Config = toml::from_str(text).unwrap();
+        let dumped: Config = toml::from_str(&config.dump()).unwrap();
+
+        let keys = |keys: &[&str]| keys.iter().map(|k| k.parse().unwrap()).collect::<Vec<Keys>>();
+        assert_eq!(dumped.keys.normal[&Action::Top], keys(&["g g", "Home"]));
+        assert_eq!(dumped.keys.normal[&Action::Quit], keys(&[]));
+        assert!(dumped.keys.normal.len() > 2);
+        assert!(!dumped.keys.popup.is_empty());
+
+        // read back, it binds the same keys
+        let (before, after) = (Keymap::new(&config.keys), Keymap::new(&dumped.keys));
+        for context in Context::ALL {
+            assert_eq!(before.bindings(context), after.bindings(context), "{:?}", context);
+        }
+        assert_eq!(Config::default().dump(), toml::from_str::<Config>(&Config::default().dump()).unwrap().dump());
+    }
+
+    #[test]
+    fn sequences_only_in_the_tables_taking_them() {
+        let text = "[keys.normal]\nquit = [\"g q\"]\n[keys.popup]\nquit = [\"Esc\", \"g q\"]\n";
+        let e = toml::from_str::<Config>(text).unwrap_err().to_string();
+        assert!(e.contains("line 4"), "{}", e);
+        assert!(e.contains("`g q` is a sequence"), "{}", e);
+
+        let config: Config = toml::from_str("[keys.search]\nquit = [\"Esc\"]\n").unwrap();
+        assert_eq!(config.keys.search[&Action::Quit], ["Esc".parse::<Keys>().unwrap()]);
+    }
+}
@@ -7,38 +7,101 @@ use super::*;
 mod localhost {
     pub struct LocalHost;
     use super::*;
-    use std::os::unix::fs::MetadataExt;
+    use std::collections::HashMap;
+    use std::ffi::CStr;
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+    use std::sync::Mutex;
+    use lazy_static::lazy_static;
+
+    lazy_static! {
+        // every row asks, and a lookup may go through the network (nss)
+        static ref USERS: Mutex<HashMap<u32, Option<String>>> = Mutex::new(HashMap::new());
+        static ref GROUPS: Mutex<HashMap<u32, Option<String>>> = Mutex::new(HashMap::new());
+    }
+
+    fn user_name(uid: u32) -> Option<String> {
+        USERS.lock().unwrap().entry(uid).or_insert_with(|| lookup(|buf, result| unsafe {
+            let mut pwd: libc::passwd = std::mem::zeroed();
+            let rc = libc::getpwuid_r(uid, &mut pwd, buf.as_mut_ptr(), buf.len(), result);
+            (rc, pwd.pw_name)
+        })).clone()
+    }
+
+    fn group_name(gid: u32) -> Option<String> {
+        GROUPS.lock().unwrap().entry(gid).or_insert_with(|| lookup(|buf, result| unsafe {
+            let mut grp: libc::group = std::mem::zeroed();
+            let rc = libc::getgrgid_r(gid, &mut grp, buf.as_mut_ptr(), buf.len(), result);
+            (rc, grp.gr_name)
+        })).clone()
+    }
+
+    /// Run a `get*_r` call with a buffer growing until the entry fits, `call` returns the name field.
+    fn lookup<T>(call: impl Fn(&mut [libc::c_char], &mut *mut T) -> (libc::c_int, *const libc::c_char)) -> Option<String> {
+        let mut buf = vec![0; 1024];
+        loop {
+            let mut result = std::ptr::null_mut();
+            let (rc, name) = call(&mut buf, &mut result);
+            if rc == libc::ERANGE && buf.len() < 1 << 20 {
+                buf.resize(buf.len() * 2, 0);
+                continue;
+            }
+            if rc != 0 || result.is_null() || name.is_null() { return None; }
+            // the name points into `buf`, which is still alive here
+            return Some(unsafe { CStr::from_ptr(name) }.to_string_lossy().into_owned());
+        }
+    }
 
     impl File {
         fn from_host(path: PathBuf) -> io::Result<Self> {
             let name = Self::name(&path);
             let ext = Self::ext(&path);
-            let metadata = std::fs::metadata(&path)?;
+            let metadata = std::fs::symlink_metadata(&path)?;
 
-            let file_type = if metadata.is_dir() {
+            let file_type = if metadata.is_symlink() {
+                FileType::SymLink
+            } else if metadata.is_dir() {
                 FileType::Dir
             } else if metadata.is_file() {
                 FileType::File
-            } else if metadata.is_symlink() {
-                FileType::SymLink
             } else {
                 FileType::Other
             };
+            let (link_target, broken) = if metadata.is_symlink() {
+                // following fails for a missing target as well as for a loop
+                (std::fs::read_link(&path).ok(), std::fs::metadata(&path).is_err())
+            } else {
+                (None, false)
+            };
 
             let accessed = metadata.accessed().ok().map(Into::into);
             let created = metadata.created().ok().map(Into::into);
             let modified = metadata.modified().ok().map(Into::into);
             let len = metadata.len();
             let permissions = Permissions::from_mode(metadata.mode());
+            let special = Special::from_mode(metadata.mode());
+            let rdev = match special {
+                Some(Special::BlockDevice | Special::CharDevice) => Some((libc::major(metadata.rdev()), libc::minor(metadata.rdev()))),
+                _ => None,
+            };
             let metadata = Metadata {
                 accessed,
                 created,
                 modified,
                 len,
                 permissions,
+
+                uid: Some(metadata.uid()),
+                gid: Some(metadata.gid()),
+                user: user_name(metadata.uid()),
+                group: group_name(metadata.gid()),
+                inode: Some(metadata.ino()),
+                nlink: Some(metadata.nlink()),
+                blocks: Some(metadata.blocks() * 512),
+                special,
+                rdev,
             };
 
-            Ok(Self { path, name, ext, metadata, file_type })
+            Ok(Self { path, name, ext, metadata, file_type, link_target, broken })
         }
     }
 
@@ -61,12 +124,145 @@ mod localhost {
         }
 
         fn create_file(&mut self, path: &Path) -> io::Result<()> {
-            let _ = std::fs::File::create(path)?;
+            std::fs::OpenOptions::new().write(true).create_new(true).open(path)?;
             Ok(())
         }
 
         fn try_exists(&mut self, path: &Path) -> io::Result<bool> {
-            Ok(path.exists())
+            // a broken symlink is still there
+            match std::fs::symlink_metadata(path) {
+                Ok(_) => Ok(true),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+                Err(e) => Err(e),
+            }
+        }
+
+        fn stat(&mut self, path: &Path) -> io::Result<File> {
+            File::from_host(path.to_path_buf())
+        }
+
+        fn canonicalize(&mut self, path: &Path) -> io::Result<PathBuf> {
+            path.canonicalize()
+        }
+
+        fn remove(&mut self, path: &Path) -> io::Result<()> {
+            if std::fs::symlink_metadata(path)?.is_dir() {
+                std::fs::remove_dir_all(path)
+            } else {
+                std::fs::remove_file(path)
+            }
+        }
+
+        fn rename(&mut self, from: &Path, to: &Path) -> io::Result<()> {
+            std::fs::rename(from, to)
+        }
+
+        fn copy(&mut self, from: &Path, to: &Path) -> io::Result<()> {
+            let file_type = std::fs::symlink_metadata(from)?.file_type();
+            if file_type.is_symlink() {
+                std::os::unix::fs::symlink(std::fs::read_link(from)?, to)
+            } else if file_type.is_dir() {
+                std::fs::create_dir_all(to)?;
+                for entry in std::fs::read_dir(from)? {
+                    let entry = entry?;
+                    self.copy(&entry.path(), &to.join(entry.file_name()))?;
+                }
+                Ok(())
+            } else {
+                // std::fs::copy keeps the special bits of the source
+                std::fs::copy(from, to)?;
+                let mode = std::fs::metadata(to)?.permissions().mode();
+                self.set_permissions(to, Permissions::from_mode(mode).without_special())
+            }
+        }
+
+        fn symlink(&mut self, target: &Path, link: &Path) -> io::Result<()> {
+            std::os::unix::fs::symlink(target, link)
+        }
+
+        fn set_permissions(&mut self, path: &Path, permissions: Permissions) -> io::Result<()> {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(permissions.to_mode()))
+        }
+
+        fn open_read(&mut self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+            Ok(Box::new(std::fs::File::open(path)?))
+        }
+
+        fn open_write(&mut self, path: &Path) -> io::Result<Box<dyn WriteFile + '_>> {
+            Ok(Box::new(std::io::BufWriter::new(std::fs::File::create(path)?)))
+        }
+
+        fn fork(&self) -> io::Result<Box<dyn Host + Send>> {
+            Ok(Box::new(LocalHost))
+        }
+
+        fn watcher(&mut self) -> io::Result<Option<Box<dyn Watcher + Send>>> {
+            // out of inotify instances, polling still works
+            match Inotify::new() {
+                Ok(inotify) => Ok(Some(Box::new(inotify))),
+                Err(e) => {
+                    log::warn!("Fail to start inotify, poll instead: {}", e);
+                    Ok(None)
+                },
+            }
+        }
+    }
+
+    impl WriteFile for std::io::BufWriter<std::fs::File> {
+        fn finish(self: Box<Self>) -> io::Result<()> {
+            self.into_inner().map_err(|e| e.into_error())?;
+            Ok(())
+        }
+    }
+
+    /// Watch a directory with inotify, the entries change or the directory itself goes away.
+    struct Inotify {
+        fd: libc::c_int,
+        wd: Option<libc::c_int>,
+    }
+
+    impl Inotify {
+        fn new() -> io::Result<Self> {
+            let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+            if fd < 0 { return Err(io::Error::last_os_error()); }
+            Ok(Self { fd, wd: None })
+        }
+    }
+
+    impl Watcher for Inotify {
+        fn watch(&mut self, dir: &Path) -> io::Result<()> {
+            use std::os::unix::ffi::OsStrExt;
+            if let Some(wd) = self.wd.take() {
+                unsafe { libc::inotify_rm_watch(self.fd, wd); }
+            }
+            let path = std::ffi::CString::new(dir.as_os_str().as_bytes())
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+            let mask = libc::IN_CREATE | libc::IN_DELETE | libc::IN_MODIFY | libc::IN_ATTRIB | libc::IN_MOVED_FROM
+                | libc::IN_MOVED_TO | libc::IN_DELETE_SELF | libc::IN_MOVE_SELF;
+            let wd = unsafe { libc::inotify_add_watch(self.fd, path.as_ptr(), mask) };
+            if wd < 0 { return Err(io::Error::last_os_error()); }
+            self.wd = Some(wd);
+            // events of the directory watched before
+            self.changed();
+            Ok(())
+        }
+
+        fn changed(&mut self) -> bool {
+            // what changed doesn't matter, the whole listing is read again
+            let mut buf = [0u8; 4096];
+            let mut changed = false;
+            loop {
+                let n = unsafe { libc::read(self.fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
+                if n <= 0 { break; }
+                changed = true;
+            }
+            changed
+        }
+    }
+
+    impl Drop for Inotify {
+        fn drop(&mut self) {
+            unsafe { libc::close(self.fd); }
         }
     }
 }
@@ -76,3 +272,93 @@ mod localhost {
 
 }
 
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::test_util::ScratchDir;
+    use std::os::unix::fs::symlink;
+
+    fn listed(dir: &Path, name: &str) -> File {
+        LocalHost.read_dir(dir).unwrap().into_iter().find(|f| f.name == name).unwrap()
+    }
+
+    #[test]
+    fn dangling_link() {
+        let dir = ScratchDir::new("local", "dangling");
+        symlink("missing", dir.join("link")).unwrap();
+
+        for file in [listed(&dir, "link"), LocalHost.stat(&dir.join("link")).unwrap()] {
+            assert_eq!(file.file_type, FileType::SymLink);
+            assert!(file.broken);
+            assert_eq!(file.link_target.as_deref(), Some(Path::new("missing")));
+        }
+        assert!(LocalHost.try_exists(&dir.join("link")).unwrap());
+    }
+
+    #[test]
+    fn link_to_directory() {
+        let dir = ScratchDir::new("local", "to-dir");
+        std::fs::create_dir(dir.join("sub")).unwrap();
+        symlink("sub", dir.join("link")).unwrap();
+
+        let file = listed(&dir, "link");
+        assert_eq!(file.file_type, FileType::SymLink);
+        assert!(!file.broken);
+        assert_eq!(file.link_target.as_deref(), Some(Path::new("sub")));
+        let target = LocalHost.canonicalize(&file.path).unwrap();
+        assert!(LocalHost.stat(&target).unwrap().is_dir());
+    }
+
+    #[test]
+    fn link_loop() {
+        let dir = ScratchDir::new("local", "loop");
+        symlink("b", dir.join("a")).unwrap();
+        symlink("a", dir.join("b")).unwrap();
+
+        let files = LocalHost.read_dir(&dir).unwrap();
+        for name in ["a", "b"] {
+            let file = files.iter().find(|f| f.name == name).unwrap();
+            assert_eq!(file.file_type, FileType::SymLink);
+            assert!(file.broken);
+        }
+        assert!(LocalHost.canonicalize(&dir.join("a")).is_err());
+    }
+
+    #[test]
+    fn transfer_copies_links_as_links() {
+        let dir = ScratchDir::new("local", "transfer");
+        let src = dir.join("src");
+        std::fs::create_dir_all(src.join("sub")).unwrap();
+        std::fs::write(src.join("sub/file"), "content").unwrap();
+        symlink("sub", src.join("to-dir")).unwrap();
+        symlink("missing", src.join("dangling")).unwrap();
+        symlink("loop-b", src.join("loop-a")).unwrap();
+        symlink("loop-a", src.join("loop-b")).unwrap();
+
+        let copied = transfer(&mut LocalHost, &src, &mut LocalHost, &dir.join("dst")).unwrap();
+        assert_eq!(copied, "content".len() as u64);
+        let dst = dir.join("dst");
+        assert_eq!(std::fs::read_to_string(dst.join("sub/file")).unwrap(), "content");
+        for (name, target) in [("to-dir", "sub"), ("dangling", "missing"), ("loop-a", "loop-b"), ("loop-b", "loop-a")] {
+            assert!(std::fs::symlink_metadata(dst.join(name)).unwrap().is_symlink(), "{}", name);
+            assert_eq!(std::fs::read_link(dst.join(name)).unwrap(), Path::new(target));
+        }
+    }
+
+    #[test]
+    fn transfer_drops_setuid() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = ScratchDir::new("local", "setuid");
+        std::fs::write(dir.join("src"), "content").unwrap();
+        std::fs::set_permissions(dir.join("src"), std::fs::Permissions::from_mode(0o4755)).unwrap();
+
+        transfer(&mut LocalHost, &dir.join("src"), &mut LocalHost, &dir.join("dst")).unwrap();
+        let mode = std::fs::metadata(dir.join("dst")).unwrap().permissions().mode();
+        assert_eq!(mode & 0o7777, 0o755);
+
+        LocalHost.copy(&dir.join("src"), &dir.join("copy")).unwrap();
+        let mode = std::fs::metadata(dir.join("copy")).unwrap().permissions().mode();
+        assert_eq!(mode & 0o7777, 0o755);
+    }
+}
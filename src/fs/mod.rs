@@ -1,19 +1,283 @@
 // ref to: https://github.com/ogham/exa/tree/master/src/fs
 pub(crate) mod local;
+pub(crate) mod sftp;
+pub(crate) mod shared;
 
-use std::io;
+use std::io::{self, Read, Write};
 use chrono::{DateTime, Local};
 use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::time::Duration;
 
 pub use local::LocalHost;
+pub use sftp::SftpHost;
+pub use shared::SharedHost;
 
 pub trait Host {
     fn read_dir(&mut self, dir: &Path) -> io::Result<Vec<File>>;
     fn try_exists(&mut self, path: &Path) -> io::Result<bool>;
+    /// Metadata of `path` itself, symlinks are not followed.
+    fn stat(&mut self, path: &Path) -> io::Result<File>;
+    fn canonicalize(&mut self, path: &Path) -> io::Result<PathBuf>;
 
     fn create_dir(&mut self, path: &Path) -> io::Result<()>;
+    /// Create an empty file, fails when `path` already exists.
     fn create_file(&mut self, path: &Path) -> io::Result<()>;
-    // fn remove(&mut self, path: &Path);
+    /// Remove a file, a symlink or a whole directory tree.
+    fn remove(&mut self, path: &Path) -> io::Result<()>;
+    fn rename(&mut self, from: &Path, to: &Path) -> io::Result<()>;
+    /// Copy a file or a whole directory tree inside this host, symlinks are copied as links.
+    fn copy(&mut self, from: &Path, to: &Path) -> io::Result<()>;
+    /// Create `link` pointing to `target`.
+    fn symlink(&mut self, target: &Path, link: &Path) -> io::Result<()>;
+    fn set_permissions(&mut self, path: &Path, permissions: Permissions) -> io::Result<()>;
+
+    fn open_read(&mut self, path: &Path) -> io::Result<Box<dyn Read + '_>>;
+    /// Create or truncate `path` for writing, `finish` it to know whether everything was written.
+    fn open_write(&mut self, path: &Path) -> io::Result<Box<dyn WriteFile + '_>>;
+
+    /// `[user@]host` of a remote host, `None` for the local file system.
+    fn remote(&self) -> Option<&str> { None }
+    /// Open another independent handle to the same host, e.g. for a worker thread.
+    fn fork(&self) -> io::Result<Box<dyn Host + Send>>;
+    /// Notifications of changes, `None` when the host can't tell (see `watcher`).
+    fn watcher(&mut self) -> io::Result<Option<Box<dyn Watcher + Send>>> { Ok(None) }
+}
+
+/// Tells when the watched directory changes.
+pub trait Watcher {
+    /// Watch `dir` instead, changes before are forgotten.
+    fn watch(&mut self, dir: &Path) -> io::Result<()>;
+    /// Whether `dir` changed since the last call, never blocks.
+    fn changed(&mut self) -> bool;
+}
+
+/// A file open for writing.
+pub trait WriteFile: Write {
+    /// Flush and close, an error means the file may miss some of what was written.
+    fn finish(self: Box<Self>) -> io::Result<()>;
+}
+
+impl<H: Host + ?Sized> Host for Box<H> {
+    fn read_dir(&mut self, dir: &Path) -> io::Result<Vec<File>> { (**self).read_dir(dir) }
+    fn try_exists(&mut self, path: &Path) -> io::Result<bool> { (**self).try_exists(path) }
+    fn stat(&mut self, path: &Path) -> io::Result<File> { (**self).stat(path) }
+    fn canonicalize(&mut self, path: &Path) -> io::Result<PathBuf> { (**self).canonicalize(path) }
+
+    fn create_dir(&mut self, path: &Path) -> io::Result<()> { (**self).create_dir(path) }
+    fn create_file(&mut self, path: &Path) -> io::Result<()> { (**self).create_file(path) }
+    fn remove(&mut self, path: &Path) -> io::Result<()> { (**self).remove(path) }
+    fn rename(&mut self, from: &Path, to: &Path) -> io::Result<()> { (**self).rename(from, to) }
+    fn copy(&mut self, from: &Path, to: &Path) -> io::Result<()> { (**self).copy(from, to) }
+    fn symlink(&mut self, target: &Path, link: &Path) -> io::Result<()> { (**self).symlink(target, link) }
+    fn set_permissions(&mut self, path: &Path, permissions: Permissions) -> io::Result<()> { (**self).set_permissions(path, permissions) }
+
+    fn open_read(&mut self, path: &Path) -> io::Result<Box<dyn Read + '_>> { (**self).open_read(path) }
+    fn open_write(&mut self, path: &Path) -> io::Result<Box<dyn WriteFile + '_>> { (**self).open_write(path) }
+
+    fn remote(&self) -> Option<&str> { (**self).remote() }
+    fn fork(&self) -> io::Result<Box<dyn Host + Send>> { (**self).fork() }
+    fn watcher(&mut self) -> io::Result<Option<Box<dyn Watcher + Send>>> { (**self).watcher() }
+}
+
+/// Open the host of a command line location, `[user@]host:path` goes to sftp (as `scp` does)
+/// and anything else is a local path.
+pub fn open(location: &str) -> io::Result<(Box<dyn Host + Send>, PathBuf)> {
+    if let Some((dest, path)) = location.split_once(':') {
+        if !dest.is_empty() && !dest.contains('/') {
+            let path = if path.is_empty() { "." } else { path };
+            return Ok((Box::new(SftpHost::connect(dest)?), PathBuf::from(path)));
+        }
+    }
+    Ok((Box::new(LocalHost), PathBuf::from(location)))
+}
+
+pub(crate) const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Reported by `transfer_with` after every chunk written.
+pub struct Chunk<'a> {
+    pub file: &'a Path,
+    pub file_len: u64,
+    pub len: u64,
+}
+
+/// Copy a file or a whole directory tree from one host to another, works with any pair of hosts
+/// since the content is streamed in chunks. Symlinks are copied as links. Return the number of bytes copied.
+pub fn transfer(src: &mut dyn Host, src_path: &Path, dst: &mut dyn Host, dst_path: &Path) -> io::Result<u64> {
+    transfer_with(src, src_path, dst, dst_path, &mut |_| Ok(()))
+}
+
+/// Same as `transfer`, `progress` is called after every chunk written, an error from it aborts
+/// the transfer.
+pub fn transfer_with(
+    src: &mut dyn Host, src_path: &Path,
+    dst: &mut dyn Host, dst_path: &Path,
+    progress: &mut dyn FnMut(Chunk) -> io::Result<()>,
+) -> io::Result<u64> {
+    let file = src.stat(src_path)?;
+    if let Some(target) = &file.link_target {
+        progress(Chunk { file: src_path, file_len: 0, len: 0 })?;
+        dst.symlink(target, dst_path)?;
+        Ok(0)
+    } else if file.is_dir() {
+        // sftp has no dedicated error for an existing directory, so check it after failing
+        if let Err(e) = dst.create_dir(dst_path) {
+            if !dst.stat(dst_path).map(|f| f.is_dir()).unwrap_or(false) {
+                return Err(e);
+            }
+        }
+
+        let mut total = 0;
+        for child in src.read_dir(src_path)? {
+            if child.is_dot_dot() { continue; }
+            total += transfer_with(src, &child.path, dst, &dst_path.join(&child.name), progress)?;
+        }
+        Ok(total)
+    } else {
+        let total = transfer_file(src, src_path, file.metadata.len, dst, dst_path, progress)?;
+        dst.set_permissions(dst_path, file.metadata.permissions.without_special())?;
+        Ok(total)
+    }
+}
+
+/// Total bytes of the files below `path`, symlinks are not followed like `transfer` does.
+pub fn tree_size(host: &mut dyn Host, path: &Path) -> io::Result<u64> {
+    tree_count(host, path, &|| false).map(|tree| tree.bytes)
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TreeSize {
+    pub bytes: u64,
+    pub files: u64,
+}
+
+/// Walk `path` like `tree_size`, and count the files too. Stop with `Interrupted` once `canceled` says so.
+pub fn tree_count(host: &mut dyn Host, path: &Path, canceled: &dyn Fn() -> bool) -> io::Result<TreeSize> {
+    if canceled() {
+        return Err(io::Error::new(io::ErrorKind::Interrupted, "canceled"));
+    }
+    let file = host.stat(path)?;
+    if file.is_dir() {
+        let mut total = TreeSize::default();
+        for child in host.read_dir(path)? {
+            if child.is_dot_dot() { continue; }
+            let tree = tree_count(host, &child.path, canceled)?;
+            total.bytes += tree.bytes;
+            total.files += tree.files;
+        }
+        Ok(total)
+    } else {
+        Ok(TreeSize { bytes: file.metadata.len, files: 1 })
+    }
+}
+
+/// Like `mkdir -p`, it is fine for `path` to be an existing directory.
+pub fn create_dir_all(host: &mut dyn Host, path: &Path) -> io::Result<()> {
+    if host.try_exists(path)? {
+        return if host.stat(path)?.is_dir() {
+            Ok(())
+        } else {
+            Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("`{}` is not a directory", path.display())))
+        };
+    }
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        create_dir_all(host, parent)?;
+    }
+    host.create_dir(path)
+}
+
+/// How often a host that can't watch is listed again.
+pub const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The watcher of `host`, or one listing the directory every `POLL_INTERVAL` through `poll_with`.
+pub fn watcher(host: &mut dyn Host, poll_with: Box<dyn Host + Send>) -> io::Result<Box<dyn Watcher + Send>> {
+    match host.watcher()? {
+        Some(watcher) => Ok(watcher),
+        None => Ok(poll_watcher(poll_with)),
+    }
+}
+
+/// A watcher listing the directory every `POLL_INTERVAL` through `host`, for when the one of the host fails.
+pub fn poll_watcher(host: Box<dyn Host + Send>) -> Box<dyn Watcher + Send> {
+    Box::new(PollWatcher::spawn(host))
+}
+
+struct PollWatcher {
+    tx: Sender<PathBuf>,
+    rx: Receiver<()>,
+}
+
+impl PollWatcher {
+    fn spawn(mut host: Box<dyn Host + Send>) -> Self {
+        let (tx, rx_dirs) = channel::<PathBuf>();
+        let (tx_changes, rx) = channel();
+
+        std::thread::spawn(move || {
+            let mut dir = None;
+            let mut last = None;
+            loop {
+                // the latest directory, quit with the watcher
+                loop {
+                    match rx_dirs.try_recv() {
+                        Ok(new) => { dir = Some(new); last = None; },
+                        Err(TryRecvError::Empty) => break,
+                        Err(TryRecvError::Disconnected) => return,
+                    }
+                }
+                if let Some(dir) = &dir {
+                    // a listing failing for a while is a change too, e.g. the directory is removed
+                    let listing = host.read_dir(dir).map(|files| files.into_iter()
+                        .map(|f| (f.path, f.metadata.len, f.metadata.modified))
+                        .collect::<Vec<_>>()).ok();
+                    if last.is_some() && last != Some(listing.clone()) && tx_changes.send(()).is_err() { return; }
+                    last = Some(listing);
+                }
+                std::thread::sleep(POLL_INTERVAL);
+            }
+        });
+
+        Self { tx, rx }
+    }
+}
+
+impl Watcher for PollWatcher {
+    fn watch(&mut self, dir: &Path) -> io::Result<()> {
+        self.tx.send(dir.to_path_buf()).map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "the poller is gone"))?;
+        while self.rx.try_recv().is_ok() { }
+        Ok(())
+    }
+
+    fn changed(&mut self) -> bool {
+        let mut changed = false;
+        while self.rx.try_recv().is_ok() { changed = true; }
+        changed
+    }
+}
+
+fn transfer_file(
+    src: &mut dyn Host, src_path: &Path, src_len: u64,
+    dst: &mut dyn Host, dst_path: &Path,
+    progress: &mut dyn FnMut(Chunk) -> io::Result<()>,
+) -> io::Result<u64> {
+    let mut reader = src.open_read(src_path)?;
+    let mut writer = dst.open_write(dst_path)?;
+
+    let mut buf = vec![0; CHUNK_SIZE];
+    let mut total = 0;
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        writer.write_all(&buf[..n])?;
+        total += n as u64;
+        progress(Chunk { file: src_path, file_len: src_len, len: n as u64 })?;
+    }
+    writer.finish()?;
+
+    Ok(total)
 }
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
@@ -34,18 +298,44 @@ pub struct File {
     pub metadata: Metadata,
 
     pub file_type: FileType,
+    /// Where a symlink points to, as written in the link.
+    pub link_target: Option<PathBuf>,
+    /// A symlink whose target is missing or never resolves (a loop).
+    pub broken: bool,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct Metadata {
     pub accessed: Option<DateTime<Local>>,
     pub created: Option<DateTime<Local>>,
     pub modified: Option<DateTime<Local>>,
     pub len: u64,
     pub permissions: Permissions,
+
+    // what a host can't tell is left `None`
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    pub user: Option<String>,
+    pub group: Option<String>,
+    pub inode: Option<u64>,
+    pub nlink: Option<u64>,
+    /// Bytes allocated on disk.
+    pub blocks: Option<u64>,
+    /// What a `FileType::Other` is.
+    pub special: Option<Special>,
+    /// Major and minor number of a device.
+    pub rdev: Option<(u32, u32)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Special {
+    BlockDevice,
+    CharDevice,
+    Fifo,
+    Socket,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default)]
 pub struct Permissions {
     pub user_read:      bool,
     pub user_write:     bool,
@@ -58,6 +348,10 @@ pub struct Permissions {
     pub other_read:     bool,
     pub other_write:    bool,
     pub other_execute:  bool,
+
+    pub sticky:         bool,
+    pub setgid:         bool,
+    pub setuid:         bool,
 }
 
 impl File {
@@ -82,14 +376,10 @@ impl File {
             path,
             name: "..".to_string(),
             ext: None,
-            metadata: Metadata {
-                accessed: None,
-                modified: None,
-                created: None,
-                len: 0,
-                permissions: Permissions::from_mode(0),
-            },
-            file_type: FileType::DotDot
+            metadata: Metadata::default(),
+            file_type: FileType::DotDot,
+            link_target: None,
+            broken: false,
         })
     }
 
@@ -111,6 +401,18 @@ impl File {
     }
 }
 
+impl Special {
+    fn from_mode(bits: u32) -> Option<Self> {
+        match bits & modes::TYPE_MASK {
+            modes::TYPE_BLOCK  => Some(Self::BlockDevice),
+            modes::TYPE_CHAR   => Some(Self::CharDevice),
+            modes::TYPE_FIFO   => Some(Self::Fifo),
+            modes::TYPE_SOCKET => Some(Self::Socket),
+            _                  => None,
+        }
+    }
+}
+
 impl Permissions {
     // check exa
     fn from_mode(bits: u32) -> Self {
@@ -129,11 +431,33 @@ impl Permissions {
             other_write:    has_bit(modes::OTHER_WRITE),
             other_execute:  has_bit(modes::OTHER_EXECUTE),
 
-            // sticky:         has_bit(modes::STICKY),
-            // setgid:         has_bit(modes::SETGID),
-            // setuid:         has_bit(modes::SETUID),
+            sticky:         has_bit(modes::STICKY),
+            setgid:         has_bit(modes::SETGID),
+            setuid:         has_bit(modes::SETUID),
         }
     }
+
+    /// Only the `rwx` bits, a copy doesn't take setuid, setgid nor sticky like `cp` without `-p`.
+    pub fn without_special(self) -> Self {
+        Self { sticky: false, setgid: false, setuid: false, ..self }
+    }
+
+    pub fn to_mode(&self) -> u32 {
+        let bit = |b, bit| if b { bit } else { 0 };
+
+        bit(self.user_read,     modes::USER_READ)     |
+        bit(self.user_write,    modes::USER_WRITE)    |
+        bit(self.user_execute,  modes::USER_EXECUTE)  |
+        bit(self.group_read,    modes::GROUP_READ)    |
+        bit(self.group_write,   modes::GROUP_WRITE)   |
+        bit(self.group_execute, modes::GROUP_EXECUTE) |
+        bit(self.other_read,    modes::OTHER_READ)    |
+        bit(self.other_write,   modes::OTHER_WRITE)   |
+        bit(self.other_execute, modes::OTHER_EXECUTE) |
+        bit(self.sticky,        modes::STICKY)        |
+        bit(self.setgid,        modes::SETGID)        |
+        bit(self.setuid,        modes::SETUID)
+    }
 }
 
 /// More readable aliases for the permission bits exposed by libc.
@@ -159,4 +483,13 @@ mod modes {
     pub const STICKY: Mode        = libc::S_ISVTX as Mode;
     pub const SETGID: Mode        = libc::S_ISGID as Mode;
     pub const SETUID: Mode        = libc::S_ISUID as Mode;
+
+    pub const TYPE_MASK: Mode     = libc::S_IFMT as Mode;
+    pub const TYPE_DIR: Mode      = libc::S_IFDIR as Mode;
+    pub const TYPE_FILE: Mode     = libc::S_IFREG as Mode;
+    pub const TYPE_LINK: Mode     = libc::S_IFLNK as Mode;
+    pub const TYPE_BLOCK: Mode    = libc::S_IFBLK as Mode;
+    pub const TYPE_CHAR: Mode     = libc::S_IFCHR as Mode;
+    pub const TYPE_FIFO: Mode     = libc::S_IFIFO as Mode;
+    pub const TYPE_SOCKET: Mode   = libc::S_IFSOCK as Mode;
 }
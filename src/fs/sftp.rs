@@ -0,0 +1,710 @@
+// ref to: https://datatracker.ietf.org/doc/html/draft-ietf-secsh-filexfer-02
+// only protocol version 3 is spoken, which is what OpenSSH implements.
+use super::*;
+
+use std::ffi::OsString;
+use std::io::{BufReader, BufWriter};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::time::{Duration, SystemTime};
+
+const VERSION: u32 = 3;
+// OpenSSH refuses reads bigger than this
+const MAX_CHUNK: u32 = 32 * 1024;
+
+mod fxp {
+    pub const INIT: u8     = 1;
+    pub const VERSION: u8  = 2;
+    pub const OPEN: u8     = 3;
+    pub const CLOSE: u8    = 4;
+    pub const READ: u8     = 5;
+    pub const WRITE: u8    = 6;
+    pub const LSTAT: u8    = 7;
+    pub const SETSTAT: u8  = 9;
+    pub const OPENDIR: u8  = 11;
+    pub const READDIR: u8  = 12;
+    pub const REMOVE: u8   = 13;
+    pub const MKDIR: u8    = 14;
+    pub const RMDIR: u8    = 15;
+    pub const REALPATH: u8 = 16;
+    pub const STAT: u8     = 17;
+    pub const RENAME: u8   = 18;
+    pub const READLINK: u8 = 19;
+    pub const SYMLINK: u8  = 20;
+
+    pub const STATUS: u8   = 101;
+    pub const HANDLE: u8   = 102;
+    pub const DATA: u8     = 103;
+    pub const NAME: u8     = 104;
+    pub const ATTRS: u8    = 105;
+}
+
+mod status {
+    pub const OK: u32                = 0;
+    pub const EOF: u32               = 1;
+    pub const NO_SUCH_FILE: u32      = 2;
+    pub const PERMISSION_DENIED: u32 = 3;
+    pub const OP_UNSUPPORTED: u32    = 8;
+}
+
+mod pflags {
+    pub const READ: u32  = 0x01;
+    pub const WRITE: u32 = 0x02;
+    pub const CREAT: u32 = 0x08;
+    pub const TRUNC: u32 = 0x10;
+    pub const EXCL: u32  = 0x20;
+}
+
+mod attr {
+    pub const SIZE: u32        = 0x01;
+    pub const UIDGID: u32      = 0x02;
+    pub const PERMISSIONS: u32 = 0x04;
+    pub const ACMODTIME: u32   = 0x08;
+    pub const EXTENDED: u32    = 0x8000_0000;
+}
+
+/// A `Host` talking sftp over the stdio of a child process, usually `ssh <dest> -s sftp`.
+pub struct SftpHost {
+    session: Session,
+    dest: String,
+    command: Vec<OsString>,
+}
+
+struct Session {
+    child: Child,
+    writer: BufWriter<ChildStdin>,
+    reader: BufReader<ChildStdout>,
+    id: u32,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Attrs {
+    size: Option<u64>,
+    uid_gid: Option<(u32, u32)>,
+    permissions: Option<u32>,
+    atime_mtime: Option<(u32, u32)>,
+}
+
+/// Name, `ls -l` like long name and attributes of an entry.
+type DirEntry = (Vec<u8>, Vec<u8>, Attrs);
+
+impl SftpHost {
+    /// Connect to `dest` (`[user@]host`) through the `ssh` client.
+    pub fn connect(dest: &str) -> io::Result<Self> {
+        Self::spawn(dest, ["ssh", dest, "-s", "sftp"])
+    }
+
+    /// Speak sftp with any program on its stdio, e.g. a local OpenSSH `sftp-server`.
+    pub fn spawn<I, S>(dest: &str, command: I) -> io::Result<Self>
+    where I: IntoIterator<Item = S>, S: Into<OsString>
+    {
+        let command: Vec<OsString> = command.into_iter().map(Into::into).collect();
+        let session = Session::start(&command)?;
+        Ok(Self { session, dest: dest.to_owned(), command })
+    }
+}
+
+impl Session {
+    fn start(command: &[OsString]) -> io::Result<Self> {
+        let (program, args) = command.split_first()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty sftp command"))?;
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        let writer = BufWriter::new(child.stdin.take().unwrap());
+        let reader = BufReader::new(child.stdout.take().unwrap());
+        let mut session = Self { child, writer, reader, id: 0 };
+
+        if let Err(e) = session.init() {
+            let _ = session.child.kill();
+            let mut msg = String::new();
+            if let Some(mut stderr) = session.child.stderr.take() {
+                let _ = stderr.read_to_string(&mut msg);
+            }
+            let _ = session.child.wait();
+            return Err(io::Error::new(e.kind(), format!("fail to start sftp session: {} {}", e, msg.trim())));
+        }
+
+        // the subprocess keeps talking on stderr, forward it to our log
+        if let Some(stderr) = session.child.stderr.take() {
+            std::thread::spawn(move || {
+                use std::io::BufRead;
+                for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                    log::warn!("sftp: {}", line);
+                }
+            });
+        }
+
+        Ok(session)
+    }
+
+    fn init(&mut self) -> io::Result<()> {
+        let mut packet = Packet::new(fxp::INIT);
+        packet.u32(VERSION);
+        self.send(packet)?;
+
+        let (kind, mut payload) = self.recv()?;
+        if kind != fxp::VERSION {
+            return Err(bad_message(kind));
+        }
+        let version = payload.u32()?;
+        if version < VERSION {
+            return Err(io::Error::new(io::ErrorKind::Unsupported, format!("sftp version {} is not supported", version)));
+        }
+        Ok(())
+    }
+
+    fn send(&mut self, packet: Packet) -> io::Result<()> {
+        self.writer.write_all(&(packet.0.len() as u32).to_be_bytes())?;
+        self.writer.write_all(&packet.0)?;
+        self.writer.flush()
+    }
+
+    fn recv(&mut self) -> io::Result<(u8, Payload)> {
+        let mut len = [0; 4];
+        self.reader.read_exact(&mut len)?;
+        let len = u32::from_be_bytes(len) as usize;
+        if len == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "empty sftp packet"));
+        }
+        let mut buf = vec![0; len];
+        self.reader.read_exact(&mut buf)?;
+        let kind = buf[0];
+        Ok((kind, Payload { buf, pos: 1 }))
+    }
+
+    /// Send a request and wait for its response, the request id is filled in here.
+    fn request(&mut self, kind: u8, fill: impl FnOnce(&mut Packet)) -> io::Result<(u8, Payload)> {
+        self.id = self.id.wrapping_add(1);
+        let mut packet = Packet::new(kind);
+        packet.u32(self.id);
+        fill(&mut packet);
+        self.send(packet)?;
+
+        let (kind, mut payload) = self.recv()?;
+        let id = payload.u32()?;
+        if id != self.id {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("sftp response id {} mismatch {}", id, self.id)));
+        }
+        Ok((kind, payload))
+    }
+
+    fn request_status(&mut self, kind: u8, fill: impl FnOnce(&mut Packet)) -> io::Result<()> {
+        let (kind, mut payload) = self.request(kind, fill)?;
+        expect_status(kind, &mut payload)
+    }
+
+    fn request_handle(&mut self, kind: u8, fill: impl FnOnce(&mut Packet)) -> io::Result<Vec<u8>> {
+        match self.request(kind, fill)? {
+            (fxp::HANDLE, mut payload) => payload.bytes(),
+            (kind, mut payload) => Err(unexpected(kind, &mut payload)),
+        }
+    }
+
+    fn request_attrs(&mut self, kind: u8, path: &Path) -> io::Result<Attrs> {
+        match self.request(kind, |p| p.path(path))? {
+            (fxp::ATTRS, mut payload) => payload.attrs(),
+            (kind, mut payload) => Err(unexpected(kind, &mut payload)),
+        }
+    }
+
+    /// Return the first name of a NAME response.
+    fn request_name(&mut self, kind: u8, path: &Path) -> io::Result<PathBuf> {
+        match self.request(kind, |p| p.path(path))? {
+            (fxp::NAME, mut payload) => {
+                if payload.u32()? < 1 {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "sftp returns no name"));
+                }
+                Ok(PathBuf::from(OsString::from_vec(payload.bytes()?)))
+            },
+            (kind, mut payload) => Err(unexpected(kind, &mut payload)),
+        }
+    }
+
+    fn open(&mut self, path: &Path, flags: u32) -> io::Result<Vec<u8>> {
+        self.request_handle(fxp::OPEN, |p| { p.path(path); p.u32(flags); p.attrs(&Attrs::default()); })
+    }
+
+    fn close(&mut self, handle: &[u8]) -> io::Result<()> {
+        self.request_status(fxp::CLOSE, |p| p.bytes(handle))
+    }
+
+    /// Read at most `len` bytes at `offset`, an empty result means end of file.
+    fn read(&mut self, handle: &[u8], offset: u64, len: u32) -> io::Result<Vec<u8>> {
+        match self.request(fxp::READ, |p| { p.bytes(handle); p.u64(offset); p.u32(len.min(MAX_CHUNK)); })? {
+            (fxp::DATA, mut payload) => payload.bytes(),
+            (fxp::STATUS, mut payload) => {
+                match payload.u32()? {
+                    status::EOF => Ok(vec![]),
+                    code => Err(status_error(code, &mut payload)),
+                }
+            },
+            (kind, mut payload) => Err(unexpected(kind, &mut payload)),
+        }
+    }
+
+    fn write(&mut self, handle: &[u8], offset: u64, data: &[u8]) -> io::Result<()> {
+        self.request_status(fxp::WRITE, |p| { p.bytes(handle); p.u64(offset); p.bytes(data); })
+    }
+
+    fn read_dir(&mut self, dir: &Path) -> io::Result<Vec<DirEntry>> {
+        let handle = self.request_handle(fxp::OPENDIR, |p| p.path(dir))?;
+        let mut out = vec![];
+        let res = loop {
+            match self.request(fxp::READDIR, |p| p.bytes(&handle)) {
+                Ok((fxp::NAME, mut payload)) => {
+                    let count = payload.u32()?;
+                    for _ in 0..count {
+                        let name = payload.bytes()?;
+                        let longname = payload.bytes()?;
+                        let attrs = payload.attrs()?;
+                        out.push((name, longname, attrs));
+                    }
+                },
+                Ok((fxp::STATUS, mut payload)) => {
+                    match payload.u32()? {
+                        status::EOF => break Ok(out),
+                        code => break Err(status_error(code, &mut payload)),
+                    }
+                },
+                Ok((kind, mut payload)) => break Err(unexpected(kind, &mut payload)),
+                Err(e) => break Err(e),
+            }
+        };
+        self.close(&handle)?;
+        res
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+struct Packet(Vec<u8>);
+
+struct Payload {
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl Packet {
+    fn new(kind: u8) -> Self {
+        Self(vec![kind])
+    }
+
+    fn u32(&mut self, v: u32) {
+        self.0.extend_from_slice(&v.to_be_bytes());
+    }
+
+    fn u64(&mut self, v: u64) {
+        self.0.extend_from_slice(&v.to_be_bytes());
+    }
+
+    fn bytes(&mut self, v: &[u8]) {
+        self.u32(v.len() as u32);
+        self.0.extend_from_slice(v);
+    }
+
+    fn path(&mut self, path: &Path) {
+        self.bytes(path.as_os_str().as_bytes());
+    }
+
+    fn attrs(&mut self, attrs: &Attrs) {
+        let mut flags = 0;
+        if attrs.size.is_some()        { flags |= attr::SIZE; }
+        if attrs.uid_gid.is_some()     { flags |= attr::UIDGID; }
+        if attrs.permissions.is_some() { flags |= attr::PERMISSIONS; }
+        if attrs.atime_mtime.is_some() { flags |= attr::ACMODTIME; }
+        self.u32(flags);
+
+        if let Some(size) = attrs.size { self.u64(size); }
+        if let Some((uid, gid)) = attrs.uid_gid { self.u32(uid); self.u32(gid); }
+        if let Some(permissions) = attrs.permissions { self.u32(permissions); }
+        if let Some((atime, mtime)) = attrs.atime_mtime { self.u32(atime); self.u32(mtime); }
+    }
+}
+
+impl Payload {
+    fn take(&mut self, n: usize) -> io::Result<&[u8]> {
+        if self.buf.len() < self.pos + n {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "sftp packet too short"));
+        }
+        self.pos += n;
+        Ok(&self.buf[self.pos - n..self.pos])
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn bytes(&mut self) -> io::Result<Vec<u8>> {
+        let len = self.u32()? as usize;
+        Ok(self.take(len)?.to_vec())
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        Ok(String::from_utf8_lossy(&self.bytes()?).into_owned())
+    }
+
+    fn attrs(&mut self) -> io::Result<Attrs> {
+        let flags = self.u32()?;
+        let mut attrs = Attrs::default();
+        if flags & attr::SIZE != 0 { attrs.size = Some(self.u64()?); }
+        if flags & attr::UIDGID != 0 { attrs.uid_gid = Some((self.u32()?, self.u32()?)); }
+        if flags & attr::PERMISSIONS != 0 { attrs.permissions = Some(self.u32()?); }
+        if flags & attr::ACMODTIME != 0 { attrs.atime_mtime = Some((self.u32()?, self.u32()?)); }
+        if flags & attr::EXTENDED != 0 {
+            for _ in 0..self.u32()? {
+                let _ = self.bytes()?;
+                let _ = self.bytes()?;
+            }
+        }
+        Ok(attrs)
+    }
+}
+
+fn status_error(code: u32, payload: &mut Payload) -> io::Error {
+    let msg = payload.string().unwrap_or_default();
+    let kind = match code {
+        status::EOF               => io::ErrorKind::UnexpectedEof,
+        status::NO_SUCH_FILE      => io::ErrorKind::NotFound,
+        status::PERMISSION_DENIED => io::ErrorKind::PermissionDenied,
+        status::OP_UNSUPPORTED    => io::ErrorKind::Unsupported,
+        _                         => io::ErrorKind::Other,
+    };
+    io::Error::new(kind, format!("sftp status {}: {}", code, msg))
+}
+
+fn expect_status(kind: u8, payload: &mut Payload) -> io::Result<()> {
+    if kind != fxp::STATUS {
+        return Err(bad_message(kind));
+    }
+    match payload.u32()? {
+        status::OK => Ok(()),
+        code => Err(status_error(code, payload)),
+    }
+}
+
+fn unexpected(kind: u8, payload: &mut Payload) -> io::Error {
+    if kind == fxp::STATUS {
+        match payload.u32() {
+            Ok(code) => status_error(code, payload),
+            Err(e) => e,
+        }
+    } else {
+        bad_message(kind)
+    }
+}
+
+fn bad_message(kind: u8) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("unexpected sftp packet type {}", kind))
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+impl File {
+    fn from_attrs(path: PathBuf, attrs: &Attrs) -> Self {
+        let name = Self::name(&path);
+        let ext = Self::ext(&path);
+        let mode = attrs.permissions.unwrap_or(0);
+
+        let file_type = match mode & modes::TYPE_MASK {
+            modes::TYPE_DIR  => FileType::Dir,
+            modes::TYPE_FILE => FileType::File,
+            modes::TYPE_LINK => FileType::SymLink,
+            _                => FileType::Other,
+        };
+
+        let time = |t: u32| SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(t as u64)).map(Into::into);
+        let metadata = Metadata {
+            accessed: attrs.atime_mtime.and_then(|(atime, _)| time(atime)),
+            created: None,
+            modified: attrs.atime_mtime.and_then(|(_, mtime)| time(mtime)),
+            len: attrs.size.unwrap_or(0),
+            permissions: Permissions::from_mode(mode),
+            uid: attrs.uid_gid.map(|(uid, _)| uid),
+            gid: attrs.uid_gid.map(|(_, gid)| gid),
+            special: Special::from_mode(mode),
+            ..Default::default()
+        };
+
+        Self { path, name, ext, metadata, file_type, link_target: None, broken: false }
+    }
+}
+
+/// Stream reader over an open remote handle, closed when dropped.
+struct SftpReader<'s> {
+    session: &'s mut Session,
+    handle: Vec<u8>,
+    offset: u64,
+}
+
+struct SftpWriter<'s> {
+    session: &'s mut Session,
+    handle: Vec<u8>,
+    offset: u64,
+}
+
+impl Read for SftpReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(MAX_CHUNK as usize) as u32;
+        let data = self.session.read(&self.handle, self.offset, len)?;
+        let n = data.len().min(buf.len());
+        buf[..n].copy_from_slice(&data[..n]);
+        self.offset += n as u64;
+        Ok(n)
+    }
+}
+
+impl Write for SftpWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = buf.len().min(MAX_CHUNK as usize);
+        self.session.write(&self.handle, self.offset, &buf[..n])?;
+        self.offset += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for SftpReader<'_> {
+    fn drop(&mut self) {
+        let _ = self.session.close(&self.handle);
+    }
+}
+
+impl WriteFile for SftpWriter<'_> {
+    /// The server may only report a failed write when closing.
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        let handle = std::mem::take(&mut self.handle);
+        self.session.close(&handle)
+    }
+}
+
+impl Drop for SftpWriter<'_> {
+    fn drop(&mut self) {
+        // already closed by `finish`
+        if self.handle.is_empty() { return; }
+        if let Err(e) = self.session.close(&self.handle) {
+            log::error!("Fail to close remote file: {:?}", e);
+        }
+    }
+}
+
+impl SftpHost {
+    /// Fill the target of a symlink, which costs two more requests.
+    fn read_link(&mut self, file: &mut File) {
+        if !file.is_symlink() { return; }
+        file.link_target = self.session.request_name(fxp::READLINK, &file.path).ok();
+        file.broken = self.session.request_attrs(fxp::STAT, &file.path).is_err();
+    }
+}
+
+impl Host for SftpHost {
+    fn read_dir(&mut self, dir: &Path) -> io::Result<Vec<File>> {
+        let mut out = vec![];
+        out.push(File::new_dot_dot(self.canonicalize(&dir.join(".."))?)?);
+        for (name, longname, attrs) in self.session.read_dir(dir)? {
+            if name == b"." || name == b".." { continue; }
+            let mut file = File::from_attrs(dir.join(OsString::from_vec(name)), &attrs);
+            // v3 attributes have no names of the owner nor link count, take them from `ls -l`
+            let longname = String::from_utf8_lossy(&longname);
+            let fields: Vec<_> = longname.split_whitespace().collect();
+            if fields.len() >= 9 {
+                file.metadata.nlink = fields[1].parse().ok();
+                file.metadata.user = Some(fields[2].to_string());
+                file.metadata.group = Some(fields[3].to_string());
+            }
+            self.read_link(&mut file);
+            out.push(file);
+        }
+        Ok(out)
+    }
+
+    fn try_exists(&mut self, path: &Path) -> io::Result<bool> {
+        match self.session.request_attrs(fxp::LSTAT, path) {
+            Ok(_) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn stat(&mut self, path: &Path) -> io::Result<File> {
+        let attrs = self.session.request_attrs(fxp::LSTAT, path)?;
+        let mut file = File::from_attrs(path.to_path_buf(), &attrs);
+        self.read_link(&mut file);
+        Ok(file)
+    }
+
+    fn canonicalize(&mut self, path: &Path) -> io::Result<PathBuf> {
+        let path = self.session.request_name(fxp::REALPATH, path)?;
+        // REALPATH does not check existence on every server
+        self.session.request_attrs(fxp::STAT, &path)?;
+        Ok(path)
+    }
+
+    fn create_dir(&mut self, path: &Path) -> io::Result<()> {
+        self.session.request_status(fxp::MKDIR, |p| { p.path(path); p.attrs(&Attrs::default()); })
+    }
+
+    fn create_file(&mut self, path: &Path) -> io::Result<()> {
+        let handle = self.session.open(path, pflags::WRITE | pflags::CREAT | pflags::EXCL)?;
+        self.session.close(&handle)
+    }
+
+    fn remove(&mut self, path: &Path) -> io::Result<()> {
+        if self.stat(path)?.is_dir() {
+            for (name, _, _) in self.session.read_dir(path)? {
+                if name == b"." || name == b".." { continue; }
+                self.remove(&path.join(OsString::from_vec(name)))?;
+            }
+            self.session.request_status(fxp::RMDIR, |p| p.path(path))
+        } else {
+            self.session.request_status(fxp::REMOVE, |p| p.path(path))
+        }
+    }
+
+    fn rename(&mut self, from: &Path, to: &Path) -> io::Result<()> {
+        self.session.request_status(fxp::RENAME, |p| { p.path(from); p.path(to); })
+    }
+
+    fn copy(&mut self, from: &Path, to: &Path) -> io::Result<()> {
+        let file = self.stat(from)?;
+        match file.file_type {
+            FileType::SymLink => {
+                let target = self.session.request_name(fxp::READLINK, from)?;
+                self.symlink(&target, to)
+            },
+            FileType::Dir => {
+                if let Err(e) = self.create_dir(to) {
+                    if !self.stat(to).map(|f| f.is_dir()).unwrap_or(false) {
+                        return Err(e);
+                    }
+                }
+                for (name, _, _) in self.session.read_dir(from)? {
+                    if name == b"." || name == b".." { continue; }
+                    let name = OsString::from_vec(name);
+                    self.copy(&from.join(&name), &to.join(&name))?;
+                }
+                Ok(())
+            },
+            _ => {
+                // no server side copy in version 3, pump the data through us
+                let src = self.session.open(from, pflags::READ)?;
+                let dst = match self.session.open(to, pflags::WRITE | pflags::CREAT | pflags::TRUNC) {
+                    Ok(dst) => dst,
+                    Err(e) => {
+                        let _ = self.session.close(&src);
+                        return Err(e);
+                    },
+                };
+                let mut offset = 0;
+                let res = loop {
+                    let data = match self.session.read(&src, offset, MAX_CHUNK) {
+                        Ok(data) if data.is_empty() => break Ok(()),
+                        Ok(data) => data,
+                        Err(e) => break Err(e),
+                    };
+                    if let Err(e) = self.session.write(&dst, offset, &data) { break Err(e); }
+                    offset += data.len() as u64;
+                };
+                // close both before giving up, the destination may only fail now
+                let closed = self.session.close(&src).and(self.session.close(&dst));
+                res.and(closed)?;
+                self.set_permissions(to, file.metadata.permissions.without_special())
+            },
+        }
+    }
+
+    fn symlink(&mut self, target: &Path, link: &Path) -> io::Result<()> {
+        // OpenSSH swaps the arguments compared with the draft: target first, then the link
+        self.session.request_status(fxp::SYMLINK, |p| { p.path(target); p.path(link); })
+    }
+
+    fn set_permissions(&mut self, path: &Path, permissions: Permissions) -> io::Result<()> {
+        let attrs = Attrs { permissions: Some(permissions.to_mode()), .. Default::default() };
+        self.session.request_status(fxp::SETSTAT, |p| { p.path(path); p.attrs(&attrs); })
+    }
+
+    fn open_read(&mut self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+        let handle = self.session.open(path, pflags::READ)?;
+        Ok(Box::new(SftpReader { session: &mut self.session, handle, offset: 0 }))
+    }
+
+    fn open_write(&mut self, path: &Path) -> io::Result<Box<dyn WriteFile + '_>> {
+        let handle = self.session.open(path, pflags::WRITE | pflags::CREAT | pflags::TRUNC)?;
+        Ok(Box::new(SftpWriter { session: &mut self.session, handle, offset: 0 }))
+    }
+
+    fn remote(&self) -> Option<&str> {
+        Some(&self.dest)
+    }
+
+    fn fork(&self) -> io::Result<Box<dyn Host + Send>> {
+        Ok(Box::new(Self::spawn(&self.dest, self.command.clone())?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::ScratchDir;
+
+    /// A local OpenSSH `sftp-server`, `$FILEZ_SFTP_SERVER` or one of the usual places.
+    fn sftp_server() -> SftpHost {
+        let server = std::env::var_os("FILEZ_SFTP_SERVER").map(PathBuf::from).or_else(|| [
+            "/usr/lib/openssh/sftp-server",
+            "/usr/libexec/openssh/sftp-server",
+            "/usr/libexec/sftp-server",
+            "/usr/lib/ssh/sftp-server",
+        ].into_iter().map(PathBuf::from).find(|path| path.exists()));
+        let server = server.expect("no sftp-server found, set FILEZ_SFTP_SERVER");
+        SftpHost::spawn("localhost", [server]).unwrap()
+    }
+
+    #[test]
+    #[ignore = "needs an OpenSSH sftp-server, run with --ignored"]
+    fn round_trip() {
+        let mut host = sftp_server();
+        let dir = ScratchDir::new("sftp", "round-trip");
+
+        let mut writer = host.open_write(&dir.join("file")).unwrap();
+        writer.write_all(b"content").unwrap();
+        writer.finish().unwrap();
+        assert_eq!(std::fs::read_to_string(dir.join("file")).unwrap(), "content");
+
+        let file = host.stat(&dir.join("file")).unwrap();
+        assert_eq!(file.metadata.len, "content".len() as u64);
+        assert!(!file.is_dir());
+
+        let mut text = String::new();
+        host.open_read(&dir.join("file")).unwrap().read_to_string(&mut text).unwrap();
+        assert_eq!(text, "content");
+
+        host.create_dir(&dir.join("sub")).unwrap();
+        host.rename(&dir.join("file"), &dir.join("sub/moved")).unwrap();
+        let names: Vec<_> = host.read_dir(&dir.join("sub")).unwrap().into_iter()
+            .filter(|f| !f.is_dot_dot()).map(|f| f.name).collect();
+        assert_eq!(names, ["moved"]);
+
+        host.copy(&dir.join("sub"), &dir.join("copy")).unwrap();
+        assert_eq!(std::fs::read_to_string(dir.join("copy/moved")).unwrap(), "content");
+        assert!(host.copy(&dir.join("sub/moved"), &dir.join("missing/moved")).is_err());
+
+        host.remove(&dir.join("sub")).unwrap();
+        assert!(!host.try_exists(&dir.join("sub")).unwrap());
+    }
+}
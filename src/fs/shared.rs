@@ -0,0 +1,235 @@
+use super::*;
+
+type Call = Box<dyn FnOnce(&mut dyn Host) + Send>;
+
+/// A host living on a thread of its own, the calls of every clone run there one after the other.
+/// The work in the background of a panel shares one connection this way, `fork` gives another
+/// handle to the same host instead of connecting again.
+///
+/// An open file holds the host until it is dropped, other calls wait meanwhile: don't make one
+/// from the thread holding the file.
+#[derive(Clone)]
+pub struct SharedHost {
+    tx: Sender<Call>,
+    remote: Option<String>,
+}
+
+struct SharedReader {
+    tx: Sender<usize>,
+    rx: Receiver<io::Result<Vec<u8>>>,
+}
+
+enum Written {
+    Data(Vec<u8>),
+    Flush,
+    Finish,
+}
+
+struct SharedWriter {
+    tx: Sender<Written>,
+    rx: Receiver<io::Result<usize>>,
+}
+
+fn gone() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "the shared host is gone")
+}
+
+impl SharedHost {
+    /// Move `host` to its thread, which ends with the last handle.
+    pub fn spawn(mut host: Box<dyn Host + Send>) -> Self {
+        let remote = host.remote().map(str::to_owned);
+        let (tx, rx) = channel::<Call>();
+        std::thread::spawn(move || {
+            for call in rx { call(host.as_mut()); }
+        });
+        Self { tx, remote }
+    }
+
+    fn run(&self, f: impl FnOnce(&mut dyn Host) + Send + 'static) -> io::Result<()> {
+        self.tx.send(Box::new(f)).map_err(|_| gone())
+    }
+
+    fn call<T: Send + 'static>(&self, f: impl FnOnce(&mut dyn Host) -> io::Result<T> + Send + 'static) -> io::Result<T> {
+        let (tx, rx) = channel();
+        self.run(move |host| { let _ = tx.send(f(host)); })?;
+        rx.recv().map_err(|_| gone())?
+    }
+}
+
+impl Host for SharedHost {
+    fn read_dir(&mut self, dir: &Path) -> io::Result<Vec<File>> {
+        let dir = dir.to_path_buf();
+        self.call(move |host| host.read_dir(&dir))
+    }
+
+    fn try_exists(&mut self, path: &Path) -> io::Result<bool> {
+        let path = path.to_path_buf();
+        self.call(move |host| host.try_exists(&path))
+    }
+
+    fn stat(&mut self, path: &Path) -> io::Result<File> {
+        let path = path.to_path_buf();
+        self.call(move |host| host.stat(&path))
+    }
+
+    fn canonicalize(&mut self, path: &Path) -> io::Result<PathBuf> {
+        let path = path.to_path_buf();
+        self.call(move |host| host.canonicalize(&path))
+    }
+
+    fn create_dir(&mut self, path: &Path) -> io::Result<()> {
+        let path = path.to_path_buf();
+        self.call(move |host| host.create_dir(&path))
+    }
+
+    fn create_file(&mut self, path: &Path) -> io::Result<()> {
+        let path = path.to_path_buf();
+        self.call(move |host| host.create_file(&path))
+    }
+
+    fn remove(&mut self, path: &Path) -> io::Result<()> {
+        let path = path.to_path_buf();
+        self.call(move |host| host.remove(&path))
+    }
+
+    fn rename(&mut self, from: &Path, to: &Path) -> io::Result<()> {
+        let (from, to) = (from.to_path_buf(), to.to_path_buf());
+        self.call(move |host| host.rename(&from, &to))
+    }
+
+    fn copy(&mut self, from: &Path, to: &Path) -> io::Result<()> {
+        let (from, to) = (from.to_path_buf(), to.to_path_buf());
+        self.call(move |host| host.copy(&from, &to))
+    }
+
+    fn symlink(&mut self, target: &Path, link: &Path) -> io::Result<()> {
+        let (target, link) = (target.to_path_buf(), link.to_path_buf());
+        self.call(move |host| host.symlink(&target, &link))
+    }
+
+    fn set_permissions(&mut self, path: &Path, permissions: Permissions) -> io::Result<()> {
+        let path = path.to_path_buf();
+        self.call(move |host| host.set_permissions(&path, permissions))
+    }
+
+    fn open_read(&mut self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+        let path = path.to_path_buf();
+        let (tx, rx_lens) = channel::<usize>();
+        let (tx_data, rx) = channel();
+        // serve this file alone until the reader is dropped
+        self.run(move |host| {
+            let mut reader = match host.open_read(&path) {
+                Ok(reader) => reader,
+                Err(e) => { let _ = tx_data.send(Err(e)); return; },
+            };
+            let _ = tx_data.send(Ok(vec![]));
+            for len in rx_lens {
+                let mut buf = vec![0; len];
+                let res = reader.read(&mut buf).map(|n| { buf.truncate(n); buf });
+                if tx_data.send(res).is_err() { break; }
+            }
+        })?;
+        rx.recv().map_err(|_| gone())??;
+        Ok(Box::new(SharedReader { tx, rx }))
+    }
+
+    fn open_write(&mut self, path: &Path) -> io::Result<Box<dyn WriteFile + '_>> {
+        let path = path.to_path_buf();
+        let (tx, rx_written) = channel::<Written>();
+        let (tx_res, rx) = channel();
+        // serve this file alone until the writer is finished or dropped
+        self.run(move |host| {
+            let mut writer = match host.open_write(&path) {
+                Ok(writer) => writer,
+                Err(e) => { let _ = tx_res.send(Err(e)); return; },
+            };
+            let _ = tx_res.send(Ok(0));
+            for written in rx_written {
+                let res = match written {
+                    Written::Data(data) => writer.write(&data),
+                    Written::Flush => writer.flush().map(|_| 0),
+                    Written::Finish => { let _ = tx_res.send(writer.finish().map(|_| 0)); return; },
+                };
+                if tx_res.send(res).is_err() { break; }
+            }
+        })?;
+        rx.recv().map_err(|_| gone())??;
+        Ok(Box::new(SharedWriter { tx, rx }))
+    }
+
+    fn remote(&self) -> Option<&str> {
+        self.remote.as_deref()
+    }
+
+    fn fork(&self) -> io::Result<Box<dyn Host + Send>> {
+        Ok(Box::new(self.clone()))
+    }
+
+    fn watcher(&mut self) -> io::Result<Option<Box<dyn Watcher + Send>>> {
+        self.call(|host| host.watcher())
+    }
+}
+
+impl Read for SharedReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.tx.send(buf.len()).map_err(|_| gone())?;
+        let data = self.rx.recv().map_err(|_| gone())??;
+        buf[..data.len()].copy_from_slice(&data);
+        Ok(data.len())
+    }
+}
+
+impl SharedWriter {
+    fn send(&self, written: Written) -> io::Result<usize> {
+        self.tx.send(written).map_err(|_| gone())?;
+        self.rx.recv().map_err(|_| gone())?
+    }
+}
+
+impl Write for SharedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.send(Written::Data(buf.to_vec()))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.send(Written::Flush).map(|_| ())
+    }
+}
+
+impl WriteFile for SharedWriter {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        self.send(Written::Finish).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::ScratchDir;
+    use crate::fs::LocalHost;
+
+    #[test]
+    fn handles_share_the_host() {
+        let dir = ScratchDir::new("shared", "handles");
+        let mut host = SharedHost::spawn(Box::new(LocalHost));
+        let mut other = host.fork().unwrap();
+
+        let mut writer = host.open_write(&dir.join("file")).unwrap();
+        writer.write_all(&vec![b'x'; CHUNK_SIZE * 3]).unwrap();
+        writer.finish().unwrap();
+        let mut data = vec![];
+        other.open_read(&dir.join("file")).unwrap().read_to_end(&mut data).unwrap();
+        assert_eq!(data, vec![b'x'; CHUNK_SIZE * 3]);
+
+        // calls from other threads wait for an open file
+        let reader = host.open_read(&dir.join("file")).unwrap();
+        let file = dir.join("file");
+        let waiting = std::thread::spawn(move || other.stat(&file).map(|f| f.metadata.len));
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!waiting.is_finished());
+        drop(reader);
+        assert_eq!(waiting.join().unwrap().unwrap(), CHUNK_SIZE as u64 * 3);
+
+        assert_eq!(host.open_read(Path::new("/nonexistent")).err().unwrap().kind(), io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,549 @@
+//! Operations built from the marks of an explorer panel, and the worker thread running them.
+use crate::fs::{self, Chunk, Host};
+
+use chrono::{DateTime, Local};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
+
+// don't flood the ui with a message for every chunk
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+const PAUSE_POLL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Job {
+    Copy { src: PathBuf, dst: PathBuf },
+    Move { src: PathBuf, dst: PathBuf },
+    Delete(PathBuf),
+}
+
+/// Which explorer a job comes from, the other one is its destination.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone)]
+pub enum JobEvent {
+    Queued { id: usize, side: Side, job: Job, control: Arc<Control> },
+    Started(usize),
+    Progress(Progress),
+    Conflict(Conflict),
+    Finished { id: usize, error: Option<String> },
+}
+
+#[derive(Debug, Clone)]
+pub struct Progress {
+    pub id: usize,
+    pub file: PathBuf,
+    pub file_done: u64,
+    pub file_total: u64,
+    pub done: u64,
+    pub total: u64,
+}
+
+/// What to do with a destination which already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    /// Replace it, two directories are merged instead.
+    Overwrite,
+    Skip,
+    /// Go to `name (1).ext` or the first free number.
+    Rename,
+    OverwriteIfNewer,
+    /// Skip when the contents are the same, ask again otherwise.
+    Compare,
+}
+
+#[derive(Debug, Clone)]
+pub struct Endpoint {
+    pub path: PathBuf,
+    pub len: u64,
+    pub modified: Option<DateTime<Local>>,
+    pub is_dir: bool,
+}
+
+/// Sent when the destination of a job exists, the worker waits for an answer on `reply`.
+/// The flag of the answer applies it to the rest of the batch.
+#[derive(Debug, Clone)]
+pub struct Conflict {
+    pub id: usize,
+    pub src: Endpoint,
+    pub dst: Endpoint,
+    pub note: Option<String>,
+    pub reply: Sender<(Resolution, bool)>,
+}
+
+/// Shared by the ui and the worker, the worker checks it between chunks.
+#[derive(Debug, Default)]
+pub struct Control(AtomicU8);
+
+const RUN: u8    = 0;
+const PAUSE: u8  = 1;
+const CANCEL: u8 = 2;
+
+struct Task {
+    id: usize,
+    batch: usize,
+    side: Side,
+    job: Job,
+    control: Arc<Control>,
+}
+
+/// Handle of the background thread, jobs run one by one in the order submitted.
+pub struct Worker {
+    tx: Sender<Task>,
+    tx_events: Sender<JobEvent>,
+    rx_events: Receiver<JobEvent>,
+    next_id: usize,
+    next_batch: usize,
+}
+
+impl Job {
+    /// `src_host` is the host the marks come from, `dst_host` is where uploads go.
+    /// `progress` gets every chunk copied.
+    pub fn run(
+        &self, src_host: &mut dyn Host, dst_host: &mut dyn Host,
+        progress: &mut dyn FnMut(Chunk) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let same_host = src_host.remote() == dst_host.remote();
+        self.check(same_host)?;
+        match self {
+            Self::Copy { src, dst } | Self::Move { src, dst } => {
+                if let Self::Move { .. } = self {
+                    // cheap path first, fails across devices
+                    if same_host && dst_host.rename(src, dst).is_ok() {
+                        return Ok(());
+                    }
+                    fs::transfer_with(src_host, src, dst_host, dst, progress)?;
+                    src_host.remove(src)
+                } else {
+                    fs::transfer_with(src_host, src, dst_host, dst, progress).map(|_| ())
+                }
+            },
+            Self::Delete(path) => {
+                progress(Chunk { file: path, file_len: 0, len: 0 })?;
+                src_host.remove(path)
+            },
+        }
+    }
+
+    /// Copying or moving onto itself or below itself would destroy the source.
+    fn check(&self, same_host: bool) -> io::Result<()> {
+        match self {
+            Self::Copy { src, dst } | Self::Move { src, dst } if same_host && dst == src => {
+                Err(io::Error::new(io::ErrorKind::InvalidInput, "source and destination are the same"))
+            },
+            Self::Copy { src, dst } | Self::Move { src, dst } if same_host && dst.starts_with(src) => {
+                Err(io::Error::new(io::ErrorKind::InvalidInput, "cannot copy a directory into itself"))
+            },
+            _ => Ok(()),
+        }
+    }
+
+    pub fn source(&self) -> &Path {
+        match self {
+            Self::Copy { src, .. } | Self::Move { src, .. } => src,
+            Self::Delete(path) => path,
+        }
+    }
+
+    pub fn destination(&self) -> Option<&Path> {
+        match self {
+            Self::Copy { dst, .. } | Self::Move { dst, .. } => Some(dst),
+            Self::Delete(_) => None,
+        }
+    }
+
+    fn with_destination(self, to: PathBuf) -> Self {
+        match self {
+            Self::Copy { src, .. } => Self::Copy { src, dst: to },
+            Self::Move { src, .. } => Self::Move { src, dst: to },
+            Self::Delete(path) => Self::Delete(path),
+        }
+    }
+}
+
+impl Control {
+    /// Only a running job can be paused, a canceled one stays canceled.
+    pub fn pause(&self) {
+        let _ = self.0.compare_exchange(RUN, PAUSE, Ordering::SeqCst, Ordering::SeqCst);
+    }
+
+    pub fn resume(&self) {
+        let _ = self.0.compare_exchange(PAUSE, RUN, Ordering::SeqCst, Ordering::SeqCst);
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(CANCEL, Ordering::SeqCst);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.0.load(Ordering::SeqCst) == PAUSE
+    }
+
+    pub fn is_canceled(&self) -> bool {
+        self.0.load(Ordering::SeqCst) == CANCEL
+    }
+
+    /// Block while paused, fail once canceled.
+    fn checkpoint(&self) -> io::Result<()> {
+        loop {
+            match self.0.load(Ordering::SeqCst) {
+                CANCEL => return Err(io::Error::new(io::ErrorKind::Interrupted, "canceled")),
+                PAUSE  => std::thread::sleep(PAUSE_POLL),
+                _      => return Ok(()),
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Job {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Copy { src, dst } => write!(f, "copy `{}` -> `{}`", src.display(), dst.display()),
+            Self::Move { src, dst } => write!(f, "move `{}` -> `{}`", src.display(), dst.display()),
+            Self::Delete(path) => write!(f, "delete `{}`", path.display()),
+        }
+    }
+}
+
+impl JobEvent {
+    pub fn id(&self) -> usize {
+        match self {
+            Self::Queued { id, .. } | Self::Finished { id, .. } => *id,
+            Self::Started(id) => *id,
+            Self::Progress(progress) => progress.id,
+            Self::Conflict(conflict) => conflict.id,
+        }
+    }
+}
+
+impl Worker {
+    /// `left` and `right` should be handles of their own (see `Host::fork`), the thread keeps them.
+    pub fn spawn(left: Box<dyn Host + Send>, right: Box<dyn Host + Send>) -> Self {
+        let (tx, rx) = channel();
+        let (tx_events, rx_events) = channel();
+
+        let tx_thread = tx_events.clone();
+        std::thread::spawn(move || work(rx, tx_thread, [left, right]));
+
+        Self { tx, tx_events, rx_events, next_id: 0, next_batch: 0 }
+    }
+
+    /// Jobs submitted together make a batch, "apply to all" of a conflict stops at its end.
+    pub fn submit(&mut self, side: Side, jobs: impl IntoIterator<Item = Job>) {
+        self.next_batch += 1;
+        let batch = self.next_batch;
+        for job in jobs {
+            self.next_id += 1;
+            let id = self.next_id;
+            let control = Arc::new(Control::default());
+            let _ = self.tx_events.send(JobEvent::Queued { id, side, job: job.clone(), control: control.clone() });
+            if self.tx.send(Task { id, batch, side, job, control }).is_err() {
+                log::error!("Job worker is gone, job #{} is dropped", id);
+            }
+        }
+    }
+
+    pub fn try_recv(&self) -> Option<JobEvent> {
+        self.rx_events.try_recv().ok()
+    }
+}
+
+fn work(rx: Receiver<Task>, tx: Sender<JobEvent>, mut hosts: [Box<dyn Host + Send>; 2]) {
+    let mut always: Option<(usize, Resolution)> = None;
+    for Task { id, batch, side, job, control } in rx {
+        if control.is_canceled() {
+            let _ = tx.send(JobEvent::Finished { id, error: Some("canceled".to_string()) });
+            continue;
+        }
+        let _ = tx.send(JobEvent::Started(id));
+
+        let [left, right] = &mut hosts;
+        let (src_host, dst_host) = match side {
+            Side::Left  => (left, right),
+            Side::Right => (right, left),
+        };
+
+        // before any conflict, overwriting the source with itself would delete it
+        if let Err(e) = job.check(src_host.remote() == dst_host.remote()) {
+            log::error!("Fail to {}: {}", job, e);
+            let _ = tx.send(JobEvent::Finished { id, error: Some(e.to_string()) });
+            continue;
+        }
+
+        if always.map(|(b, _)| b) != Some(batch) { always = None; }
+        let mut sticky = always.map(|(_, r)| r);
+        let resolved = resolve(id, job.clone(), src_host.as_mut(), dst_host.as_mut(), &control, &tx, &mut sticky);
+        always = sticky.map(|r| (batch, r));
+        let (job, replace) = match resolved {
+            Ok(Some(resolved)) => resolved,
+            Ok(None) => {
+                log::info!("Skip: {}", job);
+                let _ = tx.send(JobEvent::Finished { id, error: None });
+                continue;
+            },
+            Err(e) => {
+                log::error!("Fail to {}: {}", job, e);
+                let _ = tx.send(JobEvent::Finished { id, error: Some(e.to_string()) });
+                continue;
+            },
+        };
+
+        let total = match job {
+            Job::Delete(_) => 0,
+            _ => fs::tree_size(src_host.as_mut(), job.source()).unwrap_or(0),
+        };
+        // only clean up what we have created
+        let created = match job.destination() {
+            Some(dst) => !dst_host.try_exists(dst).unwrap_or(true),
+            None => false,
+        };
+
+        let mut last = Instant::now();
+        let mut progress = Progress { id, file: PathBuf::new(), file_done: 0, file_total: 0, done: 0, total };
+        let mut res = control.checkpoint().and_then(|_| job.run(src_host.as_mut(), dst_host.as_mut(), &mut |chunk| {
+            let new_file = chunk.file != progress.file;
+            if new_file {
+                progress.file = chunk.file.to_path_buf();
+                progress.file_done = 0;
+            }
+            progress.file_done += chunk.len;
+            progress.file_total = chunk.file_len;
+            progress.done += chunk.len;
+
+            if new_file || last.elapsed() > PROGRESS_INTERVAL || progress.file_done == progress.file_total {
+                last = Instant::now();
+                let _ = tx.send(JobEvent::Progress(progress.clone()));
+            }
+            control.checkpoint()
+        }));
+
+        // a replacement is written aside, what it replaces stays until it is complete
+        if res.is_err() && ((control.is_canceled() && created) || replace.is_some()) {
+            if let Some(dst) = job.destination() {
+                if let Err(e) = dst_host.remove(dst) {
+                    log::error!("Fail to remove partial `{}`: {}", dst.display(), e);
+                }
+            }
+        }
+        if let (Ok(()), Some(target), Some(tmp)) = (&res, &replace, job.destination()) {
+            res = replace_with(dst_host.as_mut(), tmp, target);
+        }
+
+        let error = res.err().map(|e| e.to_string());
+        match &error {
+            None    => log::info!("Done: {}", job),
+            Some(e) => log::error!("Fail to {}: {}", job, e),
+        }
+        let _ = tx.send(JobEvent::Finished { id, error });
+    }
+}
+
+/// Settle an existing destination with the ui before running, `None` if there is nothing left to do.
+///
+/// An overwrite goes to a temporary name, the path it replaces comes along.
+fn resolve(
+    id: usize, job: Job, src_host: &mut dyn Host, dst_host: &mut dyn Host,
+    control: &Control, tx: &Sender<JobEvent>, always: &mut Option<Resolution>,
+) -> io::Result<Option<(Job, Option<PathBuf>)>> {
+    let dst = match job.destination() {
+        Some(dst) if dst_host.try_exists(dst)? => dst.to_path_buf(),
+        _ => return Ok(Some((job, None))),
+    };
+    let src = endpoint(src_host, job.source())?;
+    let dst = endpoint(dst_host, &dst)?;
+
+    let mut note = None;
+    loop {
+        let resolution = match (*always, &note) {
+            (Some(resolution), None) => resolution,
+            _ => {
+                let (reply, rx) = channel();
+                let _ = tx.send(JobEvent::Conflict(Conflict { id, src: src.clone(), dst: dst.clone(), note: note.take(), reply }));
+                let (resolution, all) = loop {
+                    control.checkpoint()?;
+                    match rx.recv_timeout(PAUSE_POLL) {
+                        Ok(answer) => break answer,
+                        Err(RecvTimeoutError::Timeout) => { },
+                        Err(RecvTimeoutError::Disconnected) => return Ok(None),
+                    }
+                };
+                if all { *always = Some(resolution); }
+                resolution
+            },
+        };
+
+        match resolution {
+            Resolution::Skip => return Ok(None),
+            Resolution::OverwriteIfNewer if src.modified <= dst.modified => return Ok(None),
+            // two directories are merged in place
+            Resolution::Overwrite | Resolution::OverwriteIfNewer if src.is_dir && dst.is_dir => return Ok(Some((job, None))),
+            Resolution::Overwrite | Resolution::OverwriteIfNewer => {
+                let tmp = temp_name(dst_host, &dst.path)?;
+                return Ok(Some((job.with_destination(tmp), Some(dst.path))));
+            },
+            Resolution::Rename => {
+                let to = free_name(dst_host, &dst.path)?;
+                return Ok(Some((job.with_destination(to), None)));
+            },
+            Resolution::Compare => {
+                if src.is_dir || dst.is_dir {
+                    note = Some("directories are not compared".to_string());
+                } else if src.len == dst.len && same_content(src_host, &src.path, dst_host, &dst.path)? {
+                    return Ok(None);
+                } else {
+                    note = Some("the contents differ".to_string());
+                }
+            },
+        }
+    }
+}
+
+fn endpoint(host: &mut dyn Host, path: &Path) -> io::Result<Endpoint> {
+    let file = host.stat(path)?;
+    Ok(Endpoint { path: path.to_path_buf(), len: file.metadata.len, modified: file.metadata.modified, is_dir: file.is_dir() })
+}
+
+fn free_name(host: &mut dyn Host, path: &Path) -> io::Result<PathBuf> {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = path.extension().map(|ext| format!(".{}", ext.to_string_lossy())).unwrap_or_default();
+    for n in 1.. {
+        let to = path.with_file_name(format!("{} ({}){}", stem, n, ext));
+        if !host.try_exists(&to)? { return Ok(to); }
+    }
+    unreachable!()
+}
+
+/// A hidden name next to `path`, for a copy that replaces it.
+fn temp_name(host: &mut dyn Host, path: &Path) -> io::Result<PathBuf> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    for n in 0.. {
+        let to = path.with_file_name(format!(".{}.filez-{}", name, n));
+        if !host.try_exists(&to)? { return Ok(to); }
+    }
+    unreachable!()
+}
+
+/// Put the complete `tmp` in the place of `target`.
+fn replace_with(host: &mut dyn Host, tmp: &Path, target: &Path) -> io::Result<()> {
+    // a rename doesn't replace a directory, nor anything over sftp
+    if host.rename(tmp, target).is_ok() { return Ok(()); }
+    host.remove(target)?;
+    host.rename(tmp, target)
+}
+
+fn same_content(src_host: &mut dyn Host, src: &Path, dst_host: &mut dyn Host, dst: &Path) -> io::Result<bool> {
+    let mut src = src_host.open_read(src)?;
+    let mut dst = dst_host.open_read(dst)?;
+    let (mut a, mut b) = (vec![0; fs::CHUNK_SIZE], vec![0; fs::CHUNK_SIZE]);
+    loop {
+        let n = fill(&mut src, &mut a)?;
+        if n != fill(&mut dst, &mut b)? || a[..n] != b[..n] { return Ok(false); }
+        if n < a.len() { return Ok(true); }
+    }
+}
+
+// read until `buf` is full or at the end
+fn fill(reader: &mut dyn Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut n = 0;
+    while n < buf.len() {
+        match reader.read(&mut buf[n..]) {
+            Ok(0) => break,
+            Ok(k) => n += k,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => { },
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::ScratchDir;
+    use crate::fs::LocalHost;
+
+    /// Run `jobs` answering every conflict with `answer`, the errors of the jobs come back.
+    fn run(jobs: Vec<Job>, answer: Resolution) -> Vec<Option<String>> {
+        let mut worker = Worker::spawn(Box::new(LocalHost), Box::new(LocalHost));
+        let count = jobs.len();
+        worker.submit(Side::Left, jobs);
+        let mut errors = vec![];
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while errors.len() < count && Instant::now() < deadline {
+            match worker.try_recv() {
+                Some(JobEvent::Conflict(conflict)) => { conflict.reply.send((answer, false)).unwrap(); },
+                Some(JobEvent::Finished { error, .. }) => { errors.push(error); },
+                Some(_) => { },
+                None => std::thread::sleep(Duration::from_millis(5)),
+            }
+        }
+        errors
+    }
+
+    #[test]
+    fn copy_onto_itself_keeps_the_source() {
+        let dir = ScratchDir::new("job", "self");
+        let file = dir.join("a.txt");
+        std::fs::write(&file, "data").unwrap();
+        std::fs::create_dir(dir.join("d")).unwrap();
+
+        let errors = run(vec![
+            Job::Copy { src: file.clone(), dst: file.clone() },
+            Job::Move { src: file.clone(), dst: file.clone() },
+            Job::Copy { src: dir.join("d"), dst: dir.join("d/d") },
+        ], Resolution::Overwrite);
+        assert_eq!(errors.len(), 3);
+        assert!(errors.iter().all(|e| e.is_some()), "{:?}", errors);
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "data");
+        assert!(!dir.join("d/d").exists());
+    }
+
+    #[test]
+    fn overwrite_replaces_once_complete() {
+        let dir = ScratchDir::new("job", "overwrite");
+        std::fs::create_dir_all(dir.join("src/tree")).unwrap();
+        std::fs::create_dir_all(dir.join("dst/file")).unwrap();
+        std::fs::write(dir.join("src/a.txt"), "new").unwrap();
+        std::fs::write(dir.join("dst/a.txt"), "old").unwrap();
+        std::fs::write(dir.join("src/tree/b.txt"), "b").unwrap();
+        std::fs::write(dir.join("dst/tree"), "a file in the way").unwrap();
+        std::fs::write(dir.join("src/file"), "was a directory").unwrap();
+        std::fs::write(dir.join("dst/file/c.txt"), "c").unwrap();
+
+        let errors = run(vec![
+            Job::Copy { src: dir.join("src/a.txt"), dst: dir.join("dst/a.txt") },
+            Job::Copy { src: dir.join("src/tree"), dst: dir.join("dst/tree") },
+            Job::Move { src: dir.join("src/file"), dst: dir.join("dst/file") },
+        ], Resolution::Overwrite);
+        assert_eq!(errors, vec![None, None, None]);
+        assert_eq!(std::fs::read_to_string(dir.join("dst/a.txt")).unwrap(), "new");
+        assert_eq!(std::fs::read_to_string(dir.join("dst/tree/b.txt")).unwrap(), "b");
+        assert_eq!(std::fs::read_to_string(dir.join("dst/file")).unwrap(), "was a directory");
+        assert!(!dir.join("src/file").exists());
+        // no temporary left behind
+        let mut names: Vec<_> = std::fs::read_dir(dir.join("dst")).unwrap().map(|e| e.unwrap().file_name()).collect();
+        names.sort();
+        assert_eq!(names, ["a.txt", "file", "tree"]);
+    }
+
+    #[test]
+    fn failed_overwrite_keeps_the_destination() {
+        let dir = ScratchDir::new("job", "failed");
+        std::fs::write(dir.join("dst.txt"), "old").unwrap();
+        // opens fine, reading from address 0 fails
+        let errors = run(vec![
+            Job::Copy { src: PathBuf::from("/proc/self/mem"), dst: dir.join("dst.txt") },
+        ], Resolution::Overwrite);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].is_some());
+        assert_eq!(std::fs::read_to_string(dir.join("dst.txt")).unwrap(), "old");
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+    }
+}
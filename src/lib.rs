@@ -1,5 +1,10 @@
 pub mod ui;
 pub mod theme;
+pub mod ls_colors;
 pub mod fs;
+pub mod job;
 pub mod widgets;
 pub mod util;
+pub mod config;
+#[cfg(test)]
+mod test_util;
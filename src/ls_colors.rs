@@ -0,0 +1,255 @@
+use crate::fs::{File, FileType, Special};
+use std::collections::HashMap;
+use tui::style::{Color, Modifier, Style};
+
+/// The kinds of files `LS_COLORS` tells apart, by their two letters code.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Indicator {
+    Normal,
+    File,
+    Dir,
+    Link,
+    Fifo,
+    Socket,
+    Door,
+    Block,
+    Char,
+    Orphan,               // a link to nothing
+    Missing,              // what an orphan points to, the theme style when unset
+    Setuid,
+    Setgid,
+    Exec,
+    MultiHardlink,
+    Sticky,
+    OtherWritable,
+    StickyOtherWritable,
+}
+
+const CODES: [(&str, Indicator); 18] = [
+    ("no", Indicator::Normal),
+    ("fi", Indicator::File),
+    ("di", Indicator::Dir),
+    ("ln", Indicator::Link),
+    ("pi", Indicator::Fifo),
+    ("so", Indicator::Socket),
+    ("do", Indicator::Door),
+    ("bd", Indicator::Block),
+    ("cd", Indicator::Char),
+    ("or", Indicator::Orphan),
+    ("mi", Indicator::Missing),
+    ("su", Indicator::Setuid),
+    ("sg", Indicator::Setgid),
+    ("ex", Indicator::Exec),
+    ("mh", Indicator::MultiHardlink),
+    ("st", Indicator::Sticky),
+    ("ow", Indicator::OtherWritable),
+    ("tw", Indicator::StickyOtherWritable),
+];
+
+// codes of the terminal itself, and file capabilities we don't read
+const IGNORED: [&str; 6] = ["lc", "rc", "ec", "rs", "cl", "ca"];
+
+impl Indicator {
+    pub fn of(file: &File) -> Self {
+        let p = file.metadata.permissions;
+        match file.file_type {
+            FileType::Dir | FileType::DotDot => match (p.sticky, p.other_write) {
+                (true, true)  => Self::StickyOtherWritable,
+                (false, true) => Self::OtherWritable,
+                (true, false) => Self::Sticky,
+                _             => Self::Dir,
+            },
+            FileType::SymLink if file.broken => Self::Orphan,
+            FileType::SymLink => Self::Link,
+            FileType::Other => match file.metadata.special {
+                Some(Special::Fifo)        => Self::Fifo,
+                Some(Special::Socket)      => Self::Socket,
+                Some(Special::BlockDevice) => Self::Block,
+                Some(Special::CharDevice)  => Self::Char,
+                None                       => Self::Normal,
+            },
+            FileType::File if p.setuid => Self::Setuid,
+            FileType::File if p.setgid => Self::Setgid,
+            FileType::File if p.user_execute || p.group_execute || p.other_execute => Self::Exec,
+            FileType::File if file.metadata.nlink.unwrap_or(1) > 1 => Self::MultiHardlink,
+            FileType::File => Self::File,
+        }
+    }
+
+    /// For listings without metadata.
+    pub fn of_type(file_type: FileType) -> Self {
+        match file_type {
+            FileType::Dir | FileType::DotDot => Self::Dir,
+            FileType::SymLink => Self::Link,
+            FileType::Other   => Self::Normal,
+            FileType::File    => Self::File,
+        }
+    }
+
+    /// Where to look when this one has no style, like `ls` does.
+    fn fallback(self) -> Option<Self> {
+        match self {
+            Self::Setuid | Self::Setgid => Some(Self::Exec),
+            Self::Exec | Self::MultiHardlink => Some(Self::File),
+            Self::StickyOtherWritable => Some(Self::OtherWritable),
+            Self::OtherWritable | Self::Sticky => Some(Self::Dir),
+            Self::Orphan => Some(Self::Link),
+            Self::Door => Some(Self::Normal),
+            _ => None,
+        }
+    }
+}
+
+/// Styles of file names from `LS_COLORS`, in the `dircolors` format:
+/// `di=01;34:ln=01;36:*.tar=01;31:...`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LsColors {
+    indicators: HashMap<Indicator, Style>,
+    suffixes: Vec<(String, Style)>, // lower case, `*.tar.gz` is kept as `.tar.gz`
+}
+
+impl LsColors {
+    pub fn from_env() -> Self {
+        std::env::var("LS_COLORS").map(|text| Self::parse(&text)).unwrap_or_default()
+    }
+
+    /// Entries that `ls` wouldn't understand are skipped.
+    pub fn parse(text: &str) -> Self {
+        let mut colors = Self::default();
+        for entry in text.split(':') {
+            if let Some((key, codes)) = entry.split_once('=') {
+                if let Some(style) = parse_sgr(codes) {
+                    let _ = colors.set(key, style);
+                }
+            }
+        }
+        colors
+    }
+
+    /// Set the style of a code like `di` or of a pattern like `*.rs`.
+    ///
+    /// Styles without colors nor modifiers, like `fi=00`, leave the theme style.
+    pub fn set(&mut self, key: &str, style: Style) -> Result<(), String> {
+        if let Some(suffix) = key.strip_prefix('*') {
+            let suffix = suffix.to_lowercase();
+            self.suffixes.retain(|(s, _)| *s != suffix);
+            if style != Style::default() { self.suffixes.push((suffix, style)); }
+        } else if let Some((_, indicator)) = CODES.iter().find(|(code, _)| *code == key) {
+            if style != Style::default() {
+                self.indicators.insert(*indicator, style);
+            } else {
+                self.indicators.remove(indicator);
+            }
+        } else if !IGNORED.contains(&key) {
+            return Err(format!("unknown LS_COLORS key `{}`", key));
+        }
+        Ok(())
+    }
+
+    /// The style of `name`, patterns only apply to regular files, the longest one wins.
+    pub fn style(&self, indicator: Indicator, name: &str) -> Option<Style> {
+        let mut next = Some(indicator);
+        while let Some(indicator) = next {
+            if indicator == Indicator::File {
+                let name = name.to_lowercase();
+                let suffix = self.suffixes.iter()
+                    .filter(|(suffix, _)| name.ends_with(suffix.as_str()))
+                    .max_by_key(|(suffix, _)| suffix.len());
+                if let Some((_, style)) = suffix { return Some(*style); }
+            }
+            if let Some(style) = self.indicators.get(&indicator) { return Some(*style); }
+            next = indicator.fallback();
+        }
+        None
+    }
+
+    pub fn styles_mut(&mut self) -> impl Iterator<Item = &mut Style> {
+        self.indicators.values_mut().chain(self.suffixes.iter_mut().map(|(_, style)| style))
+    }
+}
+
+/// `01;38;5;208` and the like, None when a code is unknown.
+pub fn parse_sgr(codes: &str) -> Option<Style> {
+    const BASIC: [Color; 8] = [Color::Black, Color::Red, Color::Green, Color::Yellow, Color::Blue, Color::Magenta, Color::Cyan, Color::Gray];
+    const BRIGHT: [Color; 8] = [Color::DarkGray, Color::LightRed, Color::LightGreen, Color::LightYellow, Color::LightBlue, Color::LightMagenta, Color::LightCyan, Color::White];
+
+    let mut style = Style::default();
+    let mut codes = codes.split(';').filter(|code| !code.is_empty()).map(|code| code.parse::<u8>().ok());
+    while let Some(code) = codes.next() {
+        style = match code? {
+            0  => Style::default(),
+            1  => style.add_modifier(Modifier::BOLD),
+            2  => style.add_modifier(Modifier::DIM),
+            3  => style.add_modifier(Modifier::ITALIC),
+            4  => style.add_modifier(Modifier::UNDERLINED),
+            5  => style.add_modifier(Modifier::SLOW_BLINK),
+            6  => style.add_modifier(Modifier::RAPID_BLINK),
+            7  => style.add_modifier(Modifier::REVERSED),
+            8  => style.add_modifier(Modifier::HIDDEN),
+            9  => style.add_modifier(Modifier::CROSSED_OUT),
+            code @ 30..=37 => style.fg(BASIC[code as usize - 30]),
+            code @ 40..=47 => style.bg(BASIC[code as usize - 40]),
+            code @ 90..=97 => style.fg(BRIGHT[code as usize - 90]),
+            code @ 100..=107 => style.bg(BRIGHT[code as usize - 100]),
+            39 => style.fg(Color::Reset),
+            49 => style.bg(Color::Reset),
+            code @ (38 | 48) => {
+                let color = match codes.next()?? {
+                    5 => Color::Indexed(codes.next()??),
+                    2 => Color::Rgb(codes.next()??, codes.next()??, codes.next()??),
+                    _ => { return None; },
+                };
+                if code == 38 { style.fg(color) } else { style.bg(color) }
+            },
+            _ => { return None; },
+        };
+    }
+    Some(style)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sgr() {
+        assert_eq!(parse_sgr(""), Some(Style::default()));
+        assert_eq!(parse_sgr("01;34"), Some(Style::default().fg(Color::Blue).add_modifier(Modifier::BOLD)));
+        assert_eq!(parse_sgr("38;5;208"), Some(Style::default().fg(Color::Indexed(208))));
+        assert_eq!(parse_sgr("48;2;1;2;3"), Some(Style::default().bg(Color::Rgb(1, 2, 3))));
+        assert_eq!(parse_sgr("93;41"), Some(Style::default().fg(Color::LightYellow).bg(Color::Red)));
+        // a reset drops what comes before
+        assert_eq!(parse_sgr("1;31;0;4"), Some(Style::default().add_modifier(Modifier::UNDERLINED)));
+
+        for bad in ["38;5", "38", "38;2;1;2", "38;7;1", "38;5;256", "12", "1;x", "300"] {
+            assert_eq!(parse_sgr(bad), None, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn parse_and_style() {
+        let colors = LsColors::parse("di=01;34:ln=36:ex=32:*.gz=31:*.tar.gz=35:*.RS=33:fi=00:rs=0:xx=1:*.bad=38;5:nonsense");
+        let blue = Style::default().fg(Color::Blue).add_modifier(Modifier::BOLD);
+        assert_eq!(colors.style(Indicator::Dir, "src"), Some(blue));
+        // the fallbacks of `ls`
+        assert_eq!(colors.style(Indicator::StickyOtherWritable, "tmp"), Some(blue));
+        assert_eq!(colors.style(Indicator::Orphan, "gone"), Some(Style::default().fg(Color::Cyan)));
+        assert_eq!(colors.style(Indicator::Setuid, "su"), Some(Style::default().fg(Color::Green)));
+
+        // the longest suffix wins, whatever the case
+        assert_eq!(colors.style(Indicator::File, "a.tar.gz"), Some(Style::default().fg(Color::Magenta)));
+        assert_eq!(colors.style(Indicator::File, "a.gz"), Some(Style::default().fg(Color::Red)));
+        assert_eq!(colors.style(Indicator::File, "main.rs"), Some(Style::default().fg(Color::Yellow)));
+        assert_eq!(colors.style(Indicator::File, "README"), None);
+        assert_eq!(colors.style(Indicator::File, "a.bad"), None);
+        // patterns are for plain files only, `ex` goes first like in `ls`
+        assert_eq!(colors.style(Indicator::Dir, "a.gz"), Some(blue));
+        assert_eq!(colors.style(Indicator::Exec, "run.gz"), Some(Style::default().fg(Color::Green)));
+        assert_eq!(colors.style(Indicator::MultiHardlink, "a.gz"), Some(Style::default().fg(Color::Red)));
+
+        assert_eq!(LsColors::parse(""), LsColors::default());
+        let mut colors = LsColors::default();
+        assert!(colors.set("zz", Style::default()).is_err());
+        assert!(colors.set("lc", Style::default()).is_ok());
+    }
+}
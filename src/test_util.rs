@@ -0,0 +1,36 @@
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+/// An empty directory of the tests, removed with everything in it when dropped, even after a
+/// failed assertion.
+pub struct ScratchDir(PathBuf);
+
+impl ScratchDir {
+    /// Make a fresh directory named after the module and the test, it is unique to the process.
+    pub fn new(module: &str, name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("filez-{}-{}-{}", module, name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        Self(dir.canonicalize().unwrap())
+    }
+}
+
+impl Deref for ScratchDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for ScratchDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
@@ -1,57 +1,412 @@
+use crate::config::ThemeConfig;
+use crate::ls_colors::LsColors;
 use crate::ui;
-use tui::style::{Color as C, Style, Modifier as M};
+use serde::{de::Error, Deserialize, Deserializer};
+use std::collections::BTreeMap;
+use tui::style::{Color, Modifier, Style};
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
-pub enum Theme {
-    Default,
+macro_rules! styles {
+    ($($name:ident,)*) => {
+        /// Named styles of the ui, `themes/dark.toml` sets all of them.
+        #[derive(Debug, Clone, Default, PartialEq)]
+        pub struct Theme {
+            $(pub $name: Style,)*
+            /// Names by kind and extension, `$LS_COLORS` with the `[ls_colors]` of the theme on top.
+            pub ls_colors: LsColors,
+        }
+
+        impl Theme {
+            /// Set the style called `name`, false if there is none.
+            fn set(&mut self, name: &str, style: Style) -> bool {
+                match name {
+                    $(stringify!($name) => { self.$name = style; },)*
+                    _ => { return false; },
+                }
+                true
+            }
+
+            fn styles_mut(&mut self) -> impl Iterator<Item = &mut Style> {
+                vec![$(&mut self.$name,)*].into_iter().chain(self.ls_colors.styles_mut())
+            }
+        }
+    };
+}
+
+styles! {
+    // panels
+    border_active,
+    border_inactive,
+    btn,
+    progress,
+    title,
+    tabs,
+    tab_selected,
+    error,
+    warning,
+    dim,
+    flag,
+    log_trace,
+    log_debug,
+    log_info,
+    log_warn,
+    log_error,
+
+    // explorer
+    mark,
+    cursor_select,
+    selected,
+    highlight,
+    size,
+    datetime,
+    directory,
+    dot_dot,
+    file,
+    sym_link,
+    broken_link,
+    other,
+    dashed,
+    special_bit,
+    user_read,
+    user_write,
+    user_execute,
+    group_read,
+    group_write,
+    group_execute,
+    other_read,
+    other_write,
+    other_execute,
+}
+
+/// Themes shipped in the binary, the first one is the base of the others and of theme files.
+const BUILTINS: [(&str, &str); 4] = [
+    ("dark",          include_str!("themes/dark.toml")),
+    ("light",         include_str!("themes/light.toml")),
+    ("solarized",     include_str!("themes/solarized.toml")),
+    ("high-contrast", include_str!("themes/high-contrast.toml")),
+];
+
+/// A theme file, the styles it leaves out come from the theme it is based on.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ThemeFile {
+    #[serde(default)]
+    styles: BTreeMap<String, StyleSpec>,
+    /// Like in `LS_COLORS`, `di` or `*.rs` for keys, and styles of the theme.
+    #[serde(default)]
+    ls_colors: BTreeMap<String, StyleSpec>,
 }
 
-impl Default for Theme {
-    fn default() -> Self {
-        Self::Default
+/// A style written like `"blue"`, `"white on red bold"` or `"#268bd2 on 236 underlined"`.
+struct StyleSpec(Style);
+
+impl<'de> Deserialize<'de> for StyleSpec {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        parse_style(&text).map(StyleSpec).map_err(D::Error::custom)
     }
 }
 
-////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
-macro_rules! s {
-    ($c:expr) => { Style::default().fg($c) };
-    ($c:expr, $m:expr) => { Style::default().fg($c).add_modifier($m) };
+impl Theme {
+    pub fn names() -> impl Iterator<Item = &'static str> {
+        BUILTINS.iter().map(|(name, _)| *name)
+    }
+
+    /// The built-in theme `name`, then the styles of the file on top, in the colors of the terminal.
+    pub fn load(config: &ThemeConfig) -> Result<Self, String> {
+        let name = if config.name == "default" { "dark" } else { &config.name };
+        let mut theme = Self::default();
+        if config.ls_colors { theme.ls_colors = LsColors::from_env(); }
+        theme.apply(BUILTINS[0].1).expect("built-in themes are valid");
+        match BUILTINS.iter().find(|(builtin, _)| *builtin == name) {
+            Some((_, text)) => { theme.apply(text).expect("built-in themes are valid"); },
+            None => {
+                let names: Vec<_> = Self::names().collect();
+                return Err(format!("unknown theme `{}`, the themes are {}", name, names.join(", ")));
+            },
+        }
+
+        if let Some(path) = &config.file {
+            let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            theme.apply(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        }
+
+        let depth = config.colors.resolve();
+        for style in theme.styles_mut() {
+            style.fg = style.fg.map(|color| degrade(color, depth));
+            style.bg = style.bg.map(|color| degrade(color, depth));
+        }
+        Ok(theme)
+    }
+
+    fn apply(&mut self, text: &str) -> Result<(), String> {
+        let file: ThemeFile = toml::from_str(text).map_err(|e| e.to_string())?;
+        for (name, StyleSpec(style)) in file.styles {
+            if !self.set(&name, style) {
+                return Err(format!("unknown style `{}`", name));
+            }
+        }
+        for (key, StyleSpec(style)) in file.ls_colors {
+            self.ls_colors.set(&key, style)?;
+        }
+        Ok(())
+    }
+}
+
+const COLORS: [(&str, Color); 16] = [
+    ("black",        Color::Black),
+    ("red",          Color::Red),
+    ("green",        Color::Green),
+    ("yellow",       Color::Yellow),
+    ("blue",         Color::Blue),
+    ("magenta",      Color::Magenta),
+    ("cyan",         Color::Cyan),
+    ("gray",         Color::Gray),
+    ("darkgray",     Color::DarkGray),
+    ("lightred",     Color::LightRed),
+    ("lightgreen",   Color::LightGreen),
+    ("lightyellow",  Color::LightYellow),
+    ("lightblue",    Color::LightBlue),
+    ("lightmagenta", Color::LightMagenta),
+    ("lightcyan",    Color::LightCyan),
+    ("white",        Color::White),
+];
+
+const MODIFIERS: [(&str, Modifier); 9] = [
+    ("bold",        Modifier::BOLD),
+    ("dim",         Modifier::DIM),
+    ("italic",      Modifier::ITALIC),
+    ("underlined",  Modifier::UNDERLINED),
+    ("slow_blink",  Modifier::SLOW_BLINK),
+    ("rapid_blink", Modifier::RAPID_BLINK),
+    ("reversed",    Modifier::REVERSED),
+    ("hidden",      Modifier::HIDDEN),
+    ("crossed_out", Modifier::CROSSED_OUT),
+];
+
+/// A named color, `#rrggbb`, a 256 colors index or `default` for the one of the terminal.
+pub fn parse_color(s: &str) -> Result<Color, String> {
+    let s = s.to_ascii_lowercase();
+    if s == "default" || s == "reset" {
+        return Ok(Color::Reset);
+    }
+    if let Some((_, color)) = COLORS.iter().find(|(name, _)| *name == s) {
+        return Ok(*color);
+    }
+    if let Some(hex) = s.strip_prefix('#').filter(|hex| hex.len() == 6) {
+        if let Ok(rgb) = u32::from_str_radix(hex, 16) {
+            return Ok(Color::Rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8));
+        }
+    }
+    s.parse().map(Color::Indexed).map_err(|_| format!("unknown color `{}`", s))
+}
+
+/// Colors and modifiers, the color after `on` is the background.
+pub fn parse_style(s: &str) -> Result<Style, String> {
+    let mut style = Style::default();
+    let mut words = s.split_whitespace();
+    while let Some(word) = words.next() {
+        if word == "on" {
+            let color = words.next().ok_or_else(|| format!("no color after `on` in `{}`", s))?;
+            style = style.bg(parse_color(color)?);
+        } else if let Some((_, modifier)) = MODIFIERS.iter().find(|(name, _)| *name == word) {
+            style = style.add_modifier(*modifier);
+        } else if style.fg.is_none() {
+            style = style.fg(parse_color(word)?);
+        } else {
+            return Err(format!("unexpected `{}` in `{}`", word, s));
+        }
+    }
+    Ok(style)
+}
+
+/// How many colors the terminal shows.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum ColorDepth {
+    #[default]
+    Auto,
+    TrueColor,
+    Ansi256,
+    Ansi16,
+}
+
+impl ColorDepth {
+    /// Guess `Auto` from `$COLORTERM` and `$TERM`.
+    pub fn resolve(self) -> Self {
+        if self != Self::Auto { return self; }
+        let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+        let term = std::env::var("TERM").unwrap_or_default();
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            Self::TrueColor
+        } else if term.contains("256color") || !colorterm.is_empty() {
+            Self::Ansi256
+        } else {
+            Self::Ansi16
+        }
+    }
+}
+
+impl std::str::FromStr for ColorDepth {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto"      => Ok(Self::Auto),
+            "truecolor" => Ok(Self::TrueColor),
+            "256"       => Ok(Self::Ansi256),
+            "16"        => Ok(Self::Ansi16),
+            _           => Err(format!("unknown colors `{}`, use auto, truecolor, 256 or 16", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for ColorDepth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Auto      => write!(f, "auto"),
+            Self::TrueColor => write!(f, "truecolor"),
+            Self::Ansi256   => write!(f, "256"),
+            Self::Ansi16    => write!(f, "16"),
+        }
+    }
 }
 
+/// The closest color `depth` can show.
+pub fn degrade(color: Color, depth: ColorDepth) -> Color {
+    match (color, depth) {
+        (Color::Rgb(r, g, b), ColorDepth::Ansi256) => Color::Indexed(nearest_256((r, g, b))),
+        (Color::Rgb(r, g, b), ColorDepth::Ansi16) => nearest_16((r, g, b)),
+        (Color::Indexed(idx), ColorDepth::Ansi16) if idx >= 16 => nearest_16(rgb_256(idx)),
+        (Color::Indexed(idx), ColorDepth::Ansi16) => COLORS[idx as usize].1,
+        (color, _) => color,
+    }
+}
+
+/// The xterm palette, the first 16 vary between terminals.
+const ANSI_RGB: [(u8, u8, u8); 16] = [
+    (0, 0, 0), (205, 0, 0), (0, 205, 0), (205, 205, 0), (0, 0, 238), (205, 0, 205), (0, 205, 205), (229, 229, 229),
+    (127, 127, 127), (255, 0, 0), (0, 255, 0), (255, 255, 0), (92, 92, 255), (255, 0, 255), (0, 255, 255), (255, 255, 255),
+];
+const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn rgb_256(idx: u8) -> (u8, u8, u8) {
+    match idx {
+        0..=15 => ANSI_RGB[idx as usize],
+        16..=231 => {
+            let idx = idx - 16;
+            (CUBE[(idx / 36) as usize], CUBE[(idx / 6 % 6) as usize], CUBE[(idx % 6) as usize])
+        },
+        _ => { let level = 8 + (idx - 232) * 10; (level, level, level) },
+    }
+}
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2) as u32;
+    d(r1, r2) + d(g1, g2) + d(b1, b2)
+}
+
+/// Among the cube and the grays, the 16 first ones are left to the terminal.
+fn nearest_256(rgb: (u8, u8, u8)) -> u8 {
+    (16..=255).min_by_key(|idx| distance(rgb, rgb_256(*idx))).unwrap()
+}
+
+fn nearest_16(rgb: (u8, u8, u8)) -> Color {
+    let idx = (0..16).min_by_key(|idx| distance(rgb, ANSI_RGB[*idx])).unwrap();
+    COLORS[idx].1
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
 impl ui::PanelStyles for Theme {
-    fn border_active(&self) -> Style { s!(C::Green) }
-    fn border_inactive(&self) -> Style { s!(C::White) }
-    fn btn(&self) -> Style { Style::default().bg(C::Red) }
+    fn border_active(&self) -> Style { self.border_active }
+    fn border_inactive(&self) -> Style { self.border_inactive }
+    fn btn(&self) -> Style { self.btn }
+    fn progress(&self) -> Style { self.progress }
+    fn title(&self) -> Style { self.title }
+    fn tabs(&self) -> Style { self.tabs }
+    fn tab_selected(&self) -> Style { self.tab_selected }
+    fn error(&self) -> Style { self.error }
+    fn warning(&self) -> Style { self.warning }
+    fn dim(&self) -> Style { self.dim }
+    fn log(&self, level: log::Level) -> Style {
+        match level {
+            log::Level::Trace => self.log_trace,
+            log::Level::Debug => self.log_debug,
+            log::Level::Info  => self.log_info,
+            log::Level::Warn  => self.log_warn,
+            log::Level::Error => self.log_error,
+        }
+    }
 }
 
 impl ui::FeStyles for Theme {
-    fn mark(&self) -> Style  { s!(C::Red) }
-    fn cursor_select(&self) -> Style { Style::default().bg(C::Yellow) }
+    fn mark(&self) -> Style { self.mark }
+    fn cursor_select(&self) -> Style { self.cursor_select }
+    fn selected(&self) -> Style { self.selected }
+    fn flag(&self) -> Style { self.flag }
 
-    fn highlight(&self) -> Style { Style::default().fg(C::White).bg(C::Red) }
+    fn highlight(&self) -> Style { self.highlight }
 
     // size
-    fn size(&self) -> Style { s!(C::Green) }
+    fn size(&self) -> Style { self.size }
 
     // datetime
-    fn datetime(&self) -> Style { s!(C::Blue) }
+    fn datetime(&self) -> Style { self.datetime }
 
     // file_type
-    fn directory(&self) -> Style { s!(C::Blue) }
-    fn dot_dot(&self) -> Style { s!(C::Blue) }
-    fn file(&self) -> Style { Style::default() }
-    fn sym_link(&self) -> Style { s!(C::Cyan) }
-    fn other(&self) -> Style { s!(C::Yellow) }
+    fn directory(&self) -> Style { self.directory }
+    fn dot_dot(&self) -> Style { self.dot_dot }
+    fn file(&self) -> Style { self.file }
+    fn sym_link(&self) -> Style { self.sym_link }
+    fn broken_link(&self) -> Style { self.broken_link }
+    fn other(&self) -> Style { self.other }
+    fn ls_colors(&self) -> &LsColors { &self.ls_colors }
 
     // Permissions
-    fn dashed(&self) -> Style { Style::default().add_modifier(M::DIM) }
-    fn user_read(&self) -> Style { s!(C::Yellow, M::BOLD) }
-    fn user_write(&self) -> Style { s!(C::Red, M::BOLD) }
-    fn user_execute(&self) -> Style {   s!(C::Green, M::BOLD) }
-    fn group_read(&self) -> Style { s!(C::Yellow) }
-    fn group_write(&self) -> Style { s!(C::Red) }
-    fn group_execute(&self) -> Style { s!(C::Green) }
-    fn other_read(&self) -> Style { s!(C::Yellow) }
-    fn other_write(&self) -> Style { s!(C::Red) }
-    fn other_execute(&self) -> Style { s!(C::Green) }
+    fn dashed(&self) -> Style { self.dashed }
+    fn special_bit(&self) -> Style { self.special_bit }
+    fn user_read(&self) -> Style { self.user_read }
+    fn user_write(&self) -> Style { self.user_write }
+    fn user_execute(&self) -> Style { self.user_execute }
+    fn group_read(&self) -> Style { self.group_read }
+    fn group_write(&self) -> Style { self.group_write }
+    fn group_execute(&self) -> Style { self.group_execute }
+    fn other_read(&self) -> Style { self.other_read }
+    fn other_write(&self) -> Style { self.other_write }
+    fn other_execute(&self) -> Style { self.other_execute }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn styles() {
+        assert_eq!(parse_style(""), Ok(Style::default()));
+        assert_eq!(parse_style("bold Red on #102030"), Ok(Style::default()
+            .fg(Color::Red).bg(Color::Rgb(0x10, 0x20, 0x30)).add_modifier(Modifier::BOLD)));
+        assert_eq!(parse_style("on 236 italic underlined"), Ok(Style::default()
+            .bg(Color::Indexed(236)).add_modifier(Modifier::ITALIC | Modifier::UNDERLINED)));
+        assert_eq!(parse_style("default on reset"), Ok(Style::default().fg(Color::Reset).bg(Color::Reset)));
+
+        for bad in ["red blue", "on", "bold on", "purple", "#12345", "#1234567", "256", "on #gg0000"] {
+            assert!(parse_style(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn degraded_colors() {
+        // exact matches of the cube, the grays and the 16 colors
+        assert_eq!(degrade(Color::Rgb(255, 135, 0), ColorDepth::Ansi256), Color::Indexed(208));
+        assert_eq!(degrade(Color::Rgb(128, 128, 128), ColorDepth::Ansi256), Color::Indexed(244));
+        assert_eq!(degrade(Color::Rgb(255, 0, 0), ColorDepth::Ansi16), Color::LightRed);
+        assert_eq!(degrade(Color::Rgb(200, 10, 0), ColorDepth::Ansi16), Color::Red);
+        assert_eq!(degrade(Color::Indexed(196), ColorDepth::Ansi16), Color::LightRed);
+        assert_eq!(degrade(Color::Indexed(232), ColorDepth::Ansi16), Color::Black);
+        assert_eq!(degrade(Color::Indexed(3), ColorDepth::Ansi16), Color::Yellow);
+
+        // what the terminal can show is left alone
+        assert_eq!(degrade(Color::Rgb(1, 2, 3), ColorDepth::TrueColor), Color::Rgb(1, 2, 3));
+        assert_eq!(degrade(Color::Indexed(100), ColorDepth::Ansi256), Color::Indexed(100));
+        assert_eq!(degrade(Color::Cyan, ColorDepth::Ansi16), Color::Cyan);
+        assert_eq!(degrade(Color::Reset, ColorDepth::Ansi16), Color::Reset);
+    }
 }
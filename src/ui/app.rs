@@ -1,11 +1,18 @@
 use super::*;
 
+use crate::config::Config;
 use crate::theme::Theme;
 use super::info::InfoPanel;
-use super::explorer::ExplorerPanel;
+use super::explorer::{ExplorerPanel, SearchMode};
+use super::keymap::{Action, Chord, Context, Keymap, Keys, Lookup};
 use super::status::StatusPanel;
 use super::popup::{PopupPanel, Popup};
-use crate::fs::LocalHost;
+use super::preview::PreviewPanel;
+use crate::fs::{self, Host};
+use crate::job::{Conflict, Job, Side, Worker};
+
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 enum Uid {
@@ -13,6 +20,7 @@ enum Uid {
     Remote,
     Info,
     Status,
+    Preview,
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
@@ -26,35 +34,48 @@ enum Status {
 }
 
 pub struct App {
-    p_local: ExplorerPanel<LocalHost>,
-    p_remote: ExplorerPanel<LocalHost>,
+    p_local: ExplorerPanel<Box<dyn Host + Send>>,
+    p_remote: ExplorerPanel<Box<dyn Host + Send>>,
     p_info: InfoPanel,
     p_status: StatusPanel,
     p_popup: Option<PopupPanel>,
-
+    p_preview: PreviewPanel,
+    preview: Option<Uid>, // the explorer previewed, the pane takes the place of the other one
+    history: Vec<String>, // lines entered in input popups
+    search_mode: SearchMode, // of the last search
+
+    worker: Worker,
+    conflicts: VecDeque<Conflict>, // waiting for the popup
+    jobs: HashMap<usize, (Side, Job)>, // not finished yet, to know what changes once they are
+    stale: Vec<Uid>, // explorers listed again on the next tick
     status: Status,
 
+    keymap: Keymap,
     wait: Option<Wait>,
     wait_key: Instant,
 
     // layout
     layout: [u16; 5],
+    info_height: u16,
+    leader_timeout: std::time::Duration,
     focus: Option<Uid>,
 }
 
 impl Uid {
     fn title(&self) -> &'static str {
         match self {
-            Self::Local  => "Local",
-            Self::Remote => "Remote",
-            Self::Info   => "Info",
-            Self::Status => "Status",
+            Self::Local   => "Local",
+            Self::Remote  => "Remote",
+            Self::Info    => "Info",
+            Self::Status  => "Status",
+            Self::Preview => "Preview",
         }
     }
 }
 
-pub fn run(rx_logs: Receiver<LogRecord>, left: &str, right: &str) -> io::Result<()> {
-    let mut app = App::new(left, right)?;
+/// `left` and `right` are local paths or `[user@]host:path` for sftp.
+pub fn run(rx_logs: Receiver<LogRecord>, config: &Config, theme: &Theme, left: &str, right: &str) -> io::Result<()> {
+    let mut app = App::new(config, left, right)?;
 
     // setup
     enable_raw_mode()?;
@@ -68,9 +89,7 @@ pub fn run(rx_logs: Receiver<LogRecord>, left: &str, right: &str) -> io::Result<
 
     log::info!("hello");
     let mut now = std::time::Instant::now();
-    const TO_WAIT: std::time::Duration = std::time::Duration::from_millis(15);
-
-    let theme = Theme::default();
+    let to_wait = std::time::Duration::from_millis(config.layout.frame_ms);
 
     loop {
         if app.status == Status::Exit { break; }
@@ -93,13 +112,18 @@ pub fn run(rx_logs: Receiver<LogRecord>, left: &str, right: &str) -> io::Result<
             app.on_event(Event::Log(log_record));
         }
 
+        // poll job events
+        while let Some(job_event) = app.worker.try_recv() {
+            app.on_event(Event::Job(job_event));
+        }
+
         // check fps: 60 fps
         let elapsed = now.elapsed();
-        if TO_WAIT > elapsed {
-            std::thread::sleep(TO_WAIT-elapsed);
-            now = now + TO_WAIT-elapsed;
+        if to_wait > elapsed {
+            std::thread::sleep(to_wait-elapsed);
+            now = now + to_wait-elapsed;
         }
-        terminal.draw(|f| app.draw(f, f.size(), &theme))?;
+        terminal.draw(|f| app.draw(f, f.size(), theme))?;
     }
 
     // restore
@@ -117,7 +141,7 @@ impl Ui for App {
         if rect.width < 2 || rect.height < 2*3 { return }
         let mut height = rect.height;
         let h_status = if height > 1 { 1 } else { 0 }; height -= h_status;
-        let h_info = if height > 20 { 20 } else { 0 }; height -= h_info;
+        let h_info = if height > self.info_height { self.info_height } else { 0 }; height -= h_info;
         let h_transfer = if height > 0 { height } else { 0 };
 
         let chunks = Layout::default()
@@ -142,14 +166,27 @@ impl Ui for App {
             ])
             .split(chunks[0]);
         let x0 = chunks[0].right(); let x1 = chunks[1].right();
-        let r_local = self.draw_block(f, theme, Uid::Local, chunks[0]);
-        let r_remote = self.draw_block(f, theme, Uid::Remote, chunks[1]);
+        let r_local = self.draw_block(f, theme, self.slot(Uid::Local), chunks[0]);
+        let r_remote = self.draw_block(f, theme, self.slot(Uid::Remote), chunks[1]);
 
         self.layout = [y0, y1, y2, x0, x1];
 
-        self.p_local.draw(f, r_local, theme);
-        self.p_remote.draw(f, r_remote, theme);
+        match self.preview {
+            Some(Uid::Local) => {
+                self.p_local.draw(f, r_local, theme);
+                self.p_preview.draw(f, r_remote, theme);
+            },
+            Some(Uid::Remote) => {
+                self.p_preview.draw(f, r_local, theme);
+                self.p_remote.draw(f, r_remote, theme);
+            },
+            _ => {
+                self.p_local.draw(f, r_local, theme);
+                self.p_remote.draw(f, r_remote, theme);
+            },
+        }
         self.p_info.draw(f, r_info, theme);
+        self.p_status.set_jobs(self.p_info.job_summary());
         self.p_status.draw(f, r_status, theme);
 
         if self.status == Status::Popup {
@@ -168,6 +205,7 @@ impl Ui for App {
         match &event {
             Event::Tick => { },
             Event::Log(_) => { },
+            Event::Job(_) => { },
             Event::Mouse(MouseEvent { kind: MouseEventKind::Moved , .. }) => { }, // too annoying.
             Event::Mouse(MouseEvent { kind: MouseEventKind::ScrollDown , .. }) => { }, // too annoying.
             Event::Mouse(MouseEvent { kind: MouseEventKind::ScrollUp , .. }) => { }, // too annoying.
@@ -184,19 +222,34 @@ impl Ui for App {
 }
 
 impl App {
-    fn new(left: &str, right: &str) -> io::Result<Self> {
+    fn new(config: &Config, left: &str, right: &str) -> io::Result<Self> {
+        let (h_left, d_left) = fs::open(left)?;
+        let (h_right, d_right) = fs::open(right)?;
+        let worker = Worker::spawn(h_left.fork()?, h_right.fork()?);
+        let panels = &config.panels;
         Ok(Self {
-            p_local: ExplorerPanel::new(LocalHost, left)?,
-            p_remote: ExplorerPanel::new(LocalHost, right)?,
+            p_local: ExplorerPanel::new(h_left, d_left, panels, panels.left_columns.clone())?,
+            p_remote: ExplorerPanel::new(h_right, d_right, panels, panels.right_columns.clone())?,
             p_info: InfoPanel::new(),
             p_status: StatusPanel::new(),
             p_popup: None,
-
+            p_preview: PreviewPanel::new(),
+            preview: None,
+            history: vec![],
+            search_mode: SearchMode::Substring,
+
+            worker,
+            conflicts: VecDeque::new(),
+            jobs: HashMap::new(),
+            stale: vec![],
             status: Status::Normal,
+            keymap: Keymap::new(&config.keys),
             wait: None,
             wait_key: Instant::now(),
 
             layout: Default::default(),
+            info_height: config.layout.info_height,
+            leader_timeout: std::time::Duration::from_millis(config.keys.leader_timeout_ms),
             focus: Some(Uid::Local),
         })
     }
@@ -208,6 +261,7 @@ impl App {
             Some(Uid::Local)  => {  }
             _                 => { self.focus = Some(Uid::Local); }
         }
+        self.focus = self.focus.map(|uid| self.slot(uid));
     }
 
     fn window_right(&mut self) {
@@ -217,6 +271,7 @@ impl App {
             Some(Uid::Remote) => {  },
             _                 => { self.focus = Some(Uid::Remote); }
         }
+        self.focus = self.focus.map(|uid| self.slot(uid));
     }
 
     fn window_down(&mut self) {
@@ -230,11 +285,48 @@ impl App {
 
     fn window_up(&mut self) {
         match self.focus {
-            Some(Uid::Remote) => {  }
-            Some(Uid::Local)  => {  }
-            Some(Uid::Info)   => { self.focus = Some(Uid::Local); }
-            _                 => { self.focus = Some(Uid::Local); }
+            Some(Uid::Remote)  => {  }
+            Some(Uid::Local)   => {  }
+            Some(Uid::Preview) => {  }
+            Some(Uid::Info)    => { self.focus = Some(Uid::Local); }
+            _                  => { self.focus = Some(Uid::Local); }
         }
+        self.focus = self.focus.map(|uid| self.slot(uid));
+    }
+
+    /// What is shown in the place of `uid`, the preview hides the explorer it doesn't preview.
+    fn slot(&self, uid: Uid) -> Uid {
+        match (self.preview, uid) {
+            (Some(Uid::Local), Uid::Remote) | (Some(Uid::Remote), Uid::Local) => Uid::Preview,
+            _ => uid,
+        }
+    }
+
+    /// Preview the file under the cursor of the focused explorer, false when it can't.
+    fn open_preview(&mut self) -> bool {
+        let explorer = match self.focus {
+            Some(Uid::Local)  => &self.p_local,
+            Some(Uid::Remote) => &self.p_remote,
+            _                 => { return false; }
+        };
+        self.p_preview.open(explorer.fork_host());
+        self.preview = self.focus;
+        true
+    }
+
+    /// The file under the cursor of the focused explorer.
+    fn focused_file(&self) -> Option<&fs::File> {
+        match self.focus {
+            Some(Uid::Local)  => self.p_local.current(),
+            Some(Uid::Remote) => self.p_remote.current(),
+            _                 => None,
+        }
+    }
+
+    fn close_preview(&mut self) {
+        if self.focus == Some(Uid::Preview) { self.focus = self.preview; }
+        self.preview = None;
+        self.p_preview.close();
     }
 
     fn draw_block<B: Backend>(&self, f: &mut Frame<B>, theme: &Theme, uid: Uid, rect: Rect) -> Rect {
@@ -246,12 +338,119 @@ impl App {
         return inner;
     }
 
+    /// Queue the marks of the focused explorer, uploads go to the directory of the other one.
+    fn execute_marks(&mut self) {
+        let (from, to, side) = match self.focus {
+            Some(Uid::Local)  => (&mut self.p_local, &self.p_remote, Side::Left),
+            Some(Uid::Remote) => (&mut self.p_remote, &self.p_local, Side::Right),
+            _                 => { return; }
+        };
+
+        let jobs = from.take_jobs(to.dir());
+        if jobs.is_empty() {
+            log::info!("Nothing marked to execute");
+            return;
+        }
+
+        log::info!("Queue {} tasks...", jobs.len());
+        self.worker.submit(side, jobs);
+    }
+
+    /// Explorers showing a directory touched by a finished job are listed again on the next tick.
+    fn job_finished(&mut self, id: usize) {
+        let (side, job) = match self.jobs.remove(&id) { Some(job) => job, None => return };
+        let (src, dst) = match side {
+            Side::Left  => (Uid::Local, Uid::Remote),
+            Side::Right => (Uid::Remote, Uid::Local),
+        };
+        let parent = |path: &Path| path.parent().map(Path::to_path_buf).unwrap_or_else(|| path.to_path_buf());
+        let mut touched = vec![];
+        // a copy leaves its source as it was
+        if !matches!(job, Job::Copy { .. }) { touched.push((src, parent(job.source()))); }
+        if let Some(dst_path) = job.destination() { touched.push((dst, parent(dst_path))); }
+
+        for (uid, dir) in touched {
+            let explorer = if uid == Uid::Local { &self.p_local } else { &self.p_remote };
+            // the directory itself or one below it
+            if explorer.dir().starts_with(&dir) && !self.stale.contains(&uid) {
+                self.stale.push(uid);
+            }
+        }
+    }
+
+    /// Ask for a line in a popup, `on_submit` makes the event of the answer.
+    fn ask(&mut self, title: &str, initial: String, on_submit: fn(String) -> Event) {
+        self.p_popup = Some(PopupPanel::new_input(title.to_owned(), initial, self.history.clone(), on_submit));
+        self.status = Status::Popup;
+    }
+
+    /// Lay out the focused explorer with the columns in `spec`, the other keeps its own.
+    fn set_columns(&mut self, spec: &str) {
+        let explorer = match self.focus {
+            Some(Uid::Local)  => &mut self.p_local,
+            Some(Uid::Remote) => &mut self.p_remote,
+            _                 => { return; }
+        };
+        match spec.parse() {
+            Ok(columns) => { explorer.set_columns(columns); },
+            Err(e) => {
+                let msg = format!("Bad columns `{}`: {}", spec, e);
+                log::error!("{}", msg);
+                self.p_popup = Some(PopupPanel::new_error(msg));
+                self.status = Status::Popup;
+            },
+        }
+    }
+
+    /// Filter the focused explorer with `spec`, an empty one shows everything again.
+    fn set_filter(&mut self, spec: &str) {
+        let explorer = match self.focus {
+            Some(Uid::Local)  => &mut self.p_local,
+            Some(Uid::Remote) => &mut self.p_remote,
+            _                 => { return; }
+        };
+        if spec.trim().is_empty() {
+            explorer.set_filter(None);
+            return;
+        }
+        match spec.parse() {
+            Ok(filter) => { explorer.set_filter(Some(filter)); },
+            Err(e) => {
+                let msg = format!("Bad filter `{}`: {}", spec, e);
+                log::error!("{}", msg);
+                self.p_popup = Some(PopupPanel::new_error(msg));
+                self.status = Status::Popup;
+            },
+        }
+    }
+
+    /// Create `name` in the focused explorer.
+    fn create(&mut self, name: &str, is_dir: bool) {
+        let explorer = match self.focus {
+            Some(Uid::Local)  => &mut self.p_local,
+            Some(Uid::Remote) => &mut self.p_remote,
+            _                 => { return; }
+        };
+        if name.is_empty() { return; }
+
+        match explorer.create(name, is_dir) {
+            Ok(()) => { log::info!("Created `{}`", explorer.dir().join(name).display()); },
+            Err(e) => {
+                let msg = format!("Fail to create `{}`: {}", name, e);
+                log::error!("{}", msg);
+                self.p_popup = Some(PopupPanel::new_error(msg));
+                self.status = Status::Popup;
+            },
+        }
+    }
+
     fn interact(&mut self, uid: Uid, event: Event) {
         match uid {
-            Uid::Local  => { self.p_local.on_event(event); }
-            Uid::Remote => { self.p_remote.on_event(event); }
-            Uid::Info   => { self.p_info.on_event(event); }
-            Uid::Status => { self.p_status.on_event(event); }
+            Uid::Local   => { self.p_local.on_event(event); }
+            Uid::Remote  => { self.p_remote.on_event(event); }
+            Uid::Info    => { self.p_info.on_event(event); }
+            Uid::Status  => { self.p_status.on_event(event); }
+            Uid::Preview => { self.p_preview.on_event(event); }
         }
     }
 
@@ -267,6 +466,12 @@ impl App {
                 popup.on_event(event);
                 if popup.exit() {
                     self.status = Status::Normal; // restore back to normal
+                    if let PopupPanel::Input(input) = &popup {
+                        if let Some(line) = input.submitted().filter(|line| !line.is_empty()) {
+                            self.history.retain(|l| l != line);
+                            self.history.push(line.to_owned());
+                        }
+                    }
                     if let Some(res) = popup.emit() {
                         self.on_event(res);
                     }
@@ -285,9 +490,9 @@ impl App {
 
         if row <= y0 {
             if column <= x0 {
-                return Some(Uid::Local);
+                return Some(self.slot(Uid::Local));
             } else if column <= x1 {
-                return Some(Uid::Remote);
+                return Some(self.slot(Uid::Remote));
             }
         } else if row <= y1 {
             return Some(Uid::Info);
@@ -298,6 +503,21 @@ impl App {
         None
     }
 
+    /// Run the action bound to `keys`, or wait for the rest of a sequence.
+    fn on_keys(&mut self, keys: Vec<Chord>) {
+        let contexts: &[Context] = match self.focus {
+            Some(Uid::Info)    => &[Context::Info, Context::Normal],
+            Some(Uid::Preview) => &[Context::Preview, Context::Normal],
+            _                  => &[Context::Normal],
+        };
+        match self.keymap.lookup(contexts, &keys) {
+            Lookup::Action(action) => { self.on_event(Event::Action(action)); },
+            Lookup::Pending => { self.start_wait(Wait::WaitKeys(keys)); },
+            Lookup::None if keys.len() > 1 => { log::info!("no action for keys: {}", Keys(keys)); },
+            Lookup::None => { },
+        }
+    }
+
     fn start_wait(&mut self, wait: Wait) {
         self.wait = Some(wait);
         self.wait_key = Instant::now();
@@ -314,44 +534,35 @@ impl App {
             (Wait::WaitClick(..), &Event::Mouse(MouseEvent { kind: MouseEventKind::Up(MouseButton::Left), column, row, .. })) => {
                 self.on_event(Event::Click(column, row));
             },
-            (Wait::WaitLeader(mut keys, mut idx), &Event::Key(KeyEvent { code: KeyCode::Char(code), modifiers: KeyModifiers::NONE })) => {
-                self.wait_key = Instant::now();
-                keys[idx] = code;
-                match keys {
-                    ['w', 'h', ..] => { self.window_left(); },
-                    ['w', 'l', ..] => { self.window_right(); },
-                    ['w', 'j', ..] => { self.window_down(); },
-                    ['w', 'k', ..] => { self.window_up(); },
-                    _ => {
-                        if keys.len() > idx + 1 {
-                            idx += 1;
-                            self.start_wait(Wait::WaitLeader(keys, idx));
-                        } else {
-                            log::info!("leader keys more than 5, return to normal status: {:?}", keys);
-                        }
-                    }
-                }
-            },
-            (Wait::WaitG, &Event::Key(KeyEvent { code: KeyCode::Char('g'), modifiers: KeyModifiers::NONE })) => {
-                self.on_event(Event::Keys_gg);
+            (Wait::WaitKeys(mut keys), &Event::Key(key)) => {
+                keys.push(Chord::from(key));
+                self.on_keys(keys);
             },
-            (Wait::WaitSearch(keys, idx), &Event::Key(KeyEvent { code: KeyCode::Backspace, modifiers: KeyModifiers::NONE })) => {
-                self.start_wait(Wait::WaitSearch(keys, idx.saturating_sub(1)));
-            },
-            (Wait::WaitSearch(mut keys, mut idx), &Event::Key(KeyEvent { code: KeyCode::Char(code), modifiers: KeyModifiers::NONE })) => {
-                keys[idx] = code;
-
-                if keys.len() > idx + 1 {
-                    idx += 1;
-                    self.start_wait(Wait::WaitSearch(keys, idx));
-                } else {
-                    log::info!("search keys more than {}, return to normal status: {:?}", keys.len(), keys);
+            // the panel searches at each key, Enter keeps the matches and Esc drops them
+            (Wait::WaitSearch(mode, mut query), &Event::Key(key)) => {
+                let chord = Chord::from(key);
+                match self.keymap.lookup(&[Context::Search], &[chord]) {
+                    Lookup::Action(Action::Backspace) => {
+                        query.pop();
+                        self.on_event(Event::Search(query.clone(), mode));
+                        self.start_wait(Wait::WaitSearch(mode, query));
+                    },
+                    Lookup::Action(Action::SearchMode) => {
+                        self.search_mode = mode.next();
+                        self.on_event(Event::Search(query.clone(), self.search_mode));
+                        self.start_wait(Wait::WaitSearch(self.search_mode, query));
+                    },
+                    Lookup::Action(Action::Confirm) => { },
+                    Lookup::Action(Action::Cancel) => {
+                        self.on_event(Event::Search(String::new(), mode));
+                    },
+                    _ => if let Some(c) = chord.text() {
+                        query.push(c);
+                        self.on_event(Event::Search(query.clone(), mode));
+                        self.start_wait(Wait::WaitSearch(mode, query));
+                    },
                 }
             },
-            (Wait::WaitSearch(keys, len), &Event::Key(KeyEvent { code: KeyCode::Enter, modifiers: KeyModifiers::NONE })) => {
-                let search: String = keys.into_iter().take(len).collect();
-                self.on_event(Event::Search(search));
-            },
 
             (_, Event::Key(..) | Event::Mouse(..)) => { },
             _ => unreachable!()
@@ -362,15 +573,47 @@ impl App {
         // ref event here, since event doesn't have Copy
         match (status, &event) {
             (_, Event::Tick) => {
-                if self.wait.is_some() && self.wait_key.elapsed() > std::time::Duration::from_secs(5) {
+                // typing a search may take a while
+                if self.wait.is_some() && !matches!(self.wait, Some(Wait::WaitSearch(..)))
+                    && self.wait_key.elapsed() > self.leader_timeout {
                     self.wait = None;
-                    log::info!("leader keys wait too long, return to normal status");
+                    log::info!("keys wait too long, return to normal status");
+                }
+                for uid in std::mem::take(&mut self.stale) {
+                    if uid == Uid::Local { self.p_local.refresh(); } else { self.p_remote.refresh(); }
+                }
+                self.p_local.poll();
+                self.p_remote.poll();
+                // the preview follows the cursor
+                if let Some(uid) = self.preview {
+                    let explorer = if uid == Uid::Local { &self.p_local } else { &self.p_remote };
+                    self.p_preview.show(explorer.current().map(|file| file.path.as_path()));
+                    self.p_preview.poll();
+                }
+
+                // one conflict at a time, and never over another popup
+                if self.status == Status::Normal {
+                    if let Some(conflict) = self.conflicts.pop_front() {
+                        self.p_popup = Some(PopupPanel::new_conflict(conflict));
+                        self.status = Status::Popup;
+                    }
                 }
             },
 
             (_, Event::Log(_)) => {
                 self.p_info.on_event(event);
             },
+            (_, Event::Job(JobEvent::Conflict(conflict))) => {
+                self.conflicts.push_back(conflict.clone());
+            },
+            (_, Event::Job(job_event)) => {
+                match job_event {
+                    JobEvent::Queued { id, side, job, .. } => { self.jobs.insert(*id, (*side, job.clone())); },
+                    JobEvent::Finished { id, .. } => { self.job_finished(*id); },
+                    _ => { },
+                }
+                self.p_info.on_event(event);
+            },
 
             // processing mouse events
             (_, &Event::Mouse(MouseEvent { kind: MouseEventKind::Down(MouseButton::Left), column, row, .. })) => {
@@ -387,38 +630,103 @@ impl App {
                 self.status = Status::Exit;
             },
 
-            // escap in most case
-            (Status::Normal, Event::Key(KeyEvent { code: KeyCode::Esc, .. })) => {
-                self.status = Status::Normal;
+            (Status::Normal, &Event::Key(key)) => { self.on_keys(vec![Chord::from(key)]); },
+
+            (Status::Normal, Event::Action(Action::Unfocus)) => {
                 self.focus = None;
             },
-            (Status::Normal, Event::Key(KeyEvent { code: KeyCode::Char('q'), .. })) => {
+            (Status::Normal, Event::Action(Action::Quit)) => {
                 self.p_popup = Some(PopupPanel::new_exit());
                 self.status = Status::Popup;
             },
-            (Status::Normal, Event::Key(KeyEvent { code: KeyCode::Char('?'), .. })) => {
-                self.p_popup = Some(PopupPanel::new_help());
+            (Status::Normal, Event::Action(Action::Help)) => {
+                self.p_popup = Some(PopupPanel::new_help(&self.keymap));
                 self.status = Status::Popup;
             },
+            (Status::Normal, Event::Action(Action::WindowLeft))  => { self.window_left(); },
+            (Status::Normal, Event::Action(Action::WindowRight)) => { self.window_right(); },
+            (Status::Normal, Event::Action(Action::WindowDown))  => { self.window_down(); },
+            (Status::Normal, Event::Action(Action::WindowUp))    => { self.window_up(); },
+            (Status::Normal, Event::Action(Action::Search)) => {
+                self.start_wait(Wait::WaitSearch(self.search_mode, String::new()));
+            },
 
-            // leader keys
-            (Status::Normal, &Event::Key(KeyEvent { code: KeyCode::Char(' '), modifiers: KeyModifiers::NONE })) => {
-                self.start_wait(Wait::WaitLeader([0 as char; 5], 0));
+            (Status::Normal, Event::Action(Action::Execute)) => {
+                self.execute_marks();
             },
-            // search keys
-            (Status::Normal, &Event::Key(KeyEvent { code: KeyCode::Char('/'), modifiers: KeyModifiers::NONE })) => {
-                self.start_wait(Wait::WaitSearch(Default::default(), 0));
+            (Status::Normal, Event::Action(Action::NewFile)) if matches!(self.focus, Some(Uid::Local | Uid::Remote)) => {
+                self.ask("New file", String::new(), Event::CreateFile);
             },
-
-            (Status::Normal, &Event::Key(KeyEvent { code: KeyCode::Char('G'), modifiers: KeyModifiers::SHIFT })) => {
-                self.on_event(Event::Keys_G);
+            (Status::Normal, Event::Action(Action::NewDir)) if matches!(self.focus, Some(Uid::Local | Uid::Remote)) => {
+                self.ask("New directory", String::new(), Event::CreateDir);
+            },
+            (Status::Normal, Event::Action(Action::SelectGlob)) if matches!(self.focus, Some(Uid::Local | Uid::Remote)) => {
+                self.ask("Select (glob)", "*".to_owned(), Event::SelectGlob);
+            },
+            (Status::Normal, Event::Action(Action::DeselectGlob)) if matches!(self.focus, Some(Uid::Local | Uid::Remote)) => {
+                self.ask("Unselect (glob)", "*".to_owned(), Event::DeselectGlob);
+            },
+            (Status::Normal, Event::Action(Action::Columns)) if matches!(self.focus, Some(Uid::Local | Uid::Remote)) => {
+                let columns = match self.focus {
+                    Some(Uid::Local) => self.p_local.columns(),
+                    _                => self.p_remote.columns(),
+                };
+                self.ask("Columns (name[:width],...)", columns.to_string(), Event::SetColumns);
+            },
+            (_, Event::SetColumns(spec)) => { self.set_columns(spec); },
+            (Status::Normal, Event::Action(Action::Filter)) if matches!(self.focus, Some(Uid::Local | Uid::Remote)) => {
+                let filter = match self.focus {
+                    Some(Uid::Local) => self.p_local.filter(),
+                    _                => self.p_remote.filter(),
+                };
+                let initial = filter.map(|filter| filter.to_string()).unwrap_or_default();
+                self.ask("Filter (name, glob, re:regex, size>1M, mtime<7d)", initial, Event::SetFilter);
+            },
+            (_, Event::SetFilter(spec)) => { self.set_filter(spec); },
+            (Status::Normal, Event::Action(Action::Find)) if matches!(self.focus, Some(Uid::Local | Uid::Remote)) => {
+                let explorer = if self.focus == Some(Uid::Local) { &self.p_local } else { &self.p_remote };
+                self.p_popup = Some(PopupPanel::new_finder(explorer.fork_host(), explorer.dir().to_path_buf()));
+                self.status = Status::Popup;
+            },
+            (Status::Normal, Event::Action(Action::Preview)) => {
+                if self.preview.is_some() {
+                    self.close_preview();
+                } else {
+                    self.open_preview();
+                }
             },
-            (Status::Normal, &Event::Key(KeyEvent { code: KeyCode::Char('g'), modifiers: KeyModifiers::NONE })) => {
-                self.start_wait(Wait::WaitG);
+            // files open in the preview
+            (Status::Normal, Event::Action(Action::Open)) if self.focused_file().is_some_and(|file| file.is_file()) => {
+                if self.preview.is_some() || self.open_preview() {
+                    self.focus = Some(Uid::Preview);
+                }
+            },
+            (_, Event::Reveal(path)) => {
+                match self.focus {
+                    Some(Uid::Local)  => { self.p_local.reveal(path); },
+                    Some(Uid::Remote) => { self.p_remote.reveal(path); },
+                    _                 => { },
+                }
+            },
+            (_, Event::CreateFile(name)) => { self.create(name, false); },
+            (_, Event::CreateDir(name))  => { self.create(name, true); },
+            (Status::Normal, Event::Action(Action::Retry)) if self.focus == Some(Uid::Info) => {
+                if let Some((side, job)) = self.p_info.take_retry() {
+                    log::info!("Retry: {}", job);
+                    self.worker.submit(side, [job]);
+                }
             },
 
             ////////////////////////////////////////////////////////////////////////////////////////////////////////////
-            (Status::Popup, Event::Key(..)) => { self.interact_popup(event); }
+            (Status::Popup, &Event::Key(key)) => {
+                // printable keys are typed in inputs
+                let chord = Chord::from(key);
+                let typed = chord.text().is_some() && self.p_popup.as_ref().is_some_and(|popup| popup.takes_text());
+                match self.keymap.lookup(&[Context::Popup], &[chord]) {
+                    Lookup::Action(action) if !typed => { self.interact_popup(Event::Action(action)); },
+                    _ => { self.interact_popup(event); },
+                }
+            }
             (Status::Popup, Event::Mouse(..)) => { self.interact_popup(event); }
             (Status::Popup, Event::Click(..)) => { self.interact_popup(event); }
             (Status::Popup, Event::ScrollDown) => { self.interact_popup(event); }
@@ -1,7 +1,20 @@
 use super::*;
-use crate::fs::{Permissions, File, FileType, Metadata, LocalHost, Host};
+use super::keymap::Action;
+use crate::ls_colors::Indicator;
+use crate::util::{glob_match, natural_cmp};
+use crate::fs::{self, Permissions, File, FileType, Metadata, Special, Host, SharedHost, TreeSize, Watcher};
+use crate::config::PanelsConfig;
+use crate::job::Job;
 
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use chrono::{DateTime, Local};
+use regex::{Regex, RegexBuilder};
+use unicode_segmentation::UnicodeSegmentation;
 use lazy_static::lazy_static;
 use chrono::Datelike;
 use number_prefix::NumberPrefix;
@@ -55,6 +68,11 @@ impl<T> ListView<T> {
     }
 
     ////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+    /// Index in `rows` of the row under the cursor.
+    pub fn cursor(&self) -> usize {
+        self.offset + self.select
+    }
+
     pub fn go_select_up(&mut self) {
         if self.select > 0 {
             self.select -= 1;
@@ -103,6 +121,18 @@ impl<T> ListView<T> {
         }
     }
 
+    /// Put the cursor on `rows[idx]`, scrolling only if it is out of sight.
+    pub fn go_index(&mut self, idx: usize) {
+        if idx >= self.rows.len() { return; }
+        let height = (self.rect.height as usize).max(1);
+        if idx < self.offset {
+            self.offset = idx;
+        } else if idx >= self.offset + height {
+            self.offset = idx + 1 - height;
+        }
+        self.select = idx - self.offset;
+    }
+
     pub fn go_top(&mut self) {
         self.offset = 0;
         self.select = 0;
@@ -126,25 +156,95 @@ impl<T> ListView<T> {
 
 pub struct ExplorerPanel<H> {
     host: H,
+    background: SharedHost, // one more connection, for everything done in the background
     list: ListView<RowFile>,
 
     dir: PathBuf,
+    visual: Option<usize>, // where the visual range starts, it ends at the cursor
 
     show_hidden: bool,
-    sort_by: &'static str,
+    filter: Option<Filter>,
+    sort_by: SortKey,
     sort_reverse: bool,
+    dirs_first: bool,
+
+    // size of directories
+    sizer: Option<Sizer>,
+    sizes: HashMap<PathBuf, (Option<DateTime<Local>>, TreeSize)>, // valid while the mtime is the same
+    sizing: HashSet<PathBuf>,
+    auto_size: bool,
+
+    columns: Columns,
+    search: Option<Search>,
+
+    // changes made by others
+    watcher: Option<Box<dyn Watcher + Send>>,
+    watched: Option<PathBuf>,
+    changes: Option<(Instant, Instant)>, // first and last change not refreshed yet
+}
+
+/// Walks directories in the background for their size, one at a time.
+struct Sizer {
+    tx: Sender<(PathBuf, usize)>,
+    rx: Receiver<(PathBuf, usize, io::Result<TreeSize>)>,
+    generation: Arc<AtomicUsize>, // bump to drop every walk asked before
+}
+
+impl Sizer {
+    fn spawn(mut host: Box<dyn Host + Send>) -> Self {
+        let (tx, rx_paths) = channel::<(PathBuf, usize)>();
+        let (tx_sizes, rx) = channel();
+        let generation = Arc::new(AtomicUsize::new(0));
+
+        let current = generation.clone();
+        std::thread::spawn(move || {
+            for (path, gen) in rx_paths {
+                let canceled = || current.load(Ordering::SeqCst) != gen;
+                if canceled() { continue; }
+                let res = fs::tree_count(host.as_mut(), &path, &canceled);
+                if tx_sizes.send((path, gen, res)).is_err() { break; }
+            }
+        });
+
+        Self { tx, rx, generation }
+    }
+
+    fn request(&self, path: PathBuf) {
+        let _ = self.tx.send((path, self.generation.load(Ordering::SeqCst)));
+    }
+
+    fn cancel(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+    }
 }
 
 impl<H: Host> ExplorerPanel<H> {
-    pub fn new<P: AsRef<Path>>(host: H, dir: P) -> io::Result<Self> {
+    pub fn new<P: AsRef<Path>>(host: H, dir: P, config: &PanelsConfig, columns: Columns) -> io::Result<Self> {
+        let background = SharedHost::spawn(host.fork()?);
         let mut slf = Self {
             host,
+            background,
             list: ListView::new(),
             dir: dir.as_ref().to_path_buf(),
+            visual: None,
 
-            show_hidden: false,
-            sort_by: "name",
-            sort_reverse: false,
+            show_hidden: config.show_hidden,
+            filter: None,
+            sort_by: config.sort,
+            sort_reverse: config.sort_reverse,
+            dirs_first: config.dirs_first,
+
+            sizer: None,
+            sizes: HashMap::new(),
+            sizing: HashSet::new(),
+            auto_size: config.auto_size,
+
+            columns,
+            search: None,
+
+            watcher: None,
+            watched: None,
+            changes: None,
         };
 
         slf._refresh()?;
@@ -152,8 +252,26 @@ impl<H: Host> ExplorerPanel<H> {
     }
 
     fn _refresh(&mut self) -> io::Result<()> {
-        self.dir = self.dir.canonicalize()?;
-        self.list.rows.clear();
+        let dir = self.host.canonicalize(&self.dir)?;
+        let same_dir = dir == self.dir;
+        if !same_dir {
+            self.visual = None;
+            // nobody waits for the sizes in there any more
+            if let Some(sizer) = &self.sizer { sizer.cancel(); }
+            self.sizing.clear();
+        }
+        self.dir = dir;
+        // rows move when the listing changes, the cursor and the visual range stay on their files
+        let path_at = |idx: usize| self.list.rows.get(idx).map(|rf| rf.file.path.clone());
+        let cursor = if same_dir { path_at(self.list.cursor()) } else { None };
+        let anchor = self.visual.and_then(path_at);
+        if self.watched.as_ref() != Some(&self.dir) { self.watch(); }
+        self.changes = None;
+        // keep the marks and selection of files still there, the listing may be refreshed under the user
+        let marks: std::collections::HashMap<_, _> = self.list.rows.drain(..)
+            .filter(|rf| rf.mark != Mark::None || rf.selected)
+            .map(|rf| (rf.file.path, (rf.mark, rf.selected)))
+            .collect();
         let mut rows = vec![];
         for file in self.host.read_dir(&self.dir)? {
             if !self.show_hidden {
@@ -165,36 +283,284 @@ impl<H: Host> ExplorerPanel<H> {
                     }
                 }
             }
+            if let Some(filter) = &self.filter {
+                if !file.is_dot_dot() && !filter.matches(&file) { continue; }
+            }
 
-            rows.push(RowFile { file, matched: None, mark: Mark::None });
+            let (mark, selected) = marks.get(&file.path).copied().unwrap_or((Mark::None, false));
+            let size = match self.sizes.get(&file.path) {
+                Some((modified, tree)) if *modified == file.metadata.modified => DirSize::Done(*tree),
+                _ if self.sizing.contains(&file.path) => DirSize::Pending,
+                _ => DirSize::Unknown,
+            };
+            rows.push(RowFile { file, matched: vec![], mark, selected, size });
         }
 
-        if rows.len() > 0 {
+        if !rows.is_empty() {
             rows[1..].sort_by(|r1, r2| {
                 let f1 = &r1.file;
                 let f2 = &r2.file;
 
-                let r = match self.sort_by {
-                    "name"     => std::cmp::Ord::cmp(&(f1.file_type, &f1.name),             &(f2.file_type, &f2.name)),
-                    "size"     => std::cmp::Ord::cmp(&(f1.file_type, f1.metadata.len),      &(f2.file_type, f2.metadata.len)),
-                    "modified" => std::cmp::Ord::cmp(&(f1.file_type, f1.metadata.modified), &(f2.file_type, f2.metadata.modified)),
-                    "type"     => std::cmp::Ord::cmp(&(f1.file_type, f1.metadata.len),      &(f2.file_type, f2.metadata.len)),
-                    _          => unreachable!(),
-                };
-                if self.sort_reverse { r.reverse() } else { r }
+                // directories stay on top whichever the direction
+                let group = |f: &File| f.file_type != FileType::Dir;
+                let g = if self.dirs_first { group(f1).cmp(&group(f2)) } else { std::cmp::Ordering::Equal };
+                let r = self.sort_by.cmp(f1, f2).then_with(|| f1.name.cmp(&f2.name));
+                g.then(if self.sort_reverse { r.reverse() } else { r })
             });
         }
 
+        if let Some(search) = &self.search {
+            for rf in rows.iter_mut() { rf.matched = search.find(&rf.file.name); }
+        }
         self.list.rows = rows; self.list.adjust();
+        if let Some(path) = cursor { self.go_path(&path); }
+        // the range ends with its first file
+        self.visual = anchor.and_then(|path| self.list.rows.iter().position(|rf| rf.file.path == path));
+        if self.auto_size {
+            self.size_dirs((0..self.list.rows.len()).collect());
+        }
 
         Ok(())
     }
 
+    /// List the directory again, or sort it again, the cursor stays on the same file.
     pub fn refresh(&mut self) {
         if let Err(e) = self._refresh() {
             log::error!("Fail to refresh into `{}`: {:?}", self.dir.display(), e);
         }
     }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Highlight the names matching `pattern`, the cursor moves to the first match from it.
+    /// An empty `pattern` ends the search.
+    fn search(&mut self, pattern: &str, mode: SearchMode) {
+        self.search = if pattern.is_empty() { None } else { Some(Search::new(pattern, mode)) };
+        for rf in self.list.rows.iter_mut() {
+            rf.matched = self.search.as_ref().map(|search| search.find(&rf.file.name)).unwrap_or_default();
+        }
+
+        let cursor = self.list.cursor();
+        let len = self.list.rows.len();
+        if let Some(idx) = (0..len).map(|i| (cursor + i) % len).find(|&i| !self.list.rows[i].matched.is_empty()) {
+            self.list.go_index(idx);
+        }
+    }
+
+    pub fn filter(&self) -> Option<&Filter> {
+        self.filter.as_ref()
+    }
+
+    /// Show only the entries matching `filter`, or all of them again with `None`.
+    pub fn set_filter(&mut self, filter: Option<Filter>) {
+        self.filter = filter;
+        self.refresh();
+    }
+
+    /// Go to the directory of `path` with the cursor on it.
+    pub fn reveal(&mut self, path: &Path) {
+        if let Some(parent) = path.parent() {
+            self.dir = parent.to_path_buf();
+            self.refresh();
+            if !self.go_path(path) {
+                log::error!("`{}` is gone", path.display());
+            }
+        }
+    }
+
+    /// The file under the cursor.
+    pub fn current(&self) -> Option<&File> {
+        self.list.rows.get(self.list.cursor()).map(|rf| &rf.file)
+    }
+
+    /// Another handle to the host, for the work done in the background. Every handle shares the same
+    /// connection, the panel doesn't connect again each time.
+    pub fn fork_host(&self) -> Box<dyn Host + Send> {
+        Box::new(self.background.clone())
+    }
+
+    pub fn columns(&self) -> &Columns {
+        &self.columns
+    }
+
+    pub fn set_columns(&mut self, columns: Columns) {
+        self.columns = columns;
+    }
+
+    /// Follow the changes of the current directory, without a watcher the panel just doesn't refresh itself.
+    fn watch(&mut self) {
+        self.watched = Some(self.dir.clone());
+        if self.watcher.is_none() {
+            match fs::watcher(&mut self.host, Box::new(self.background.clone())) {
+                Ok(watcher) => { self.watcher = Some(watcher); },
+                Err(e) => { log::error!("Fail to watch `{}`: {}", self.dir.display(), e); return; },
+            }
+        }
+        if let Some(Err(e)) = self.watcher.as_mut().map(|w| w.watch(&self.dir)) {
+            // e.g. out of inotify watches
+            log::warn!("Fail to watch `{}`, poll instead: {}", self.dir.display(), e);
+            let mut polled = fs::poll_watcher(self.fork_host());
+            match polled.watch(&self.dir) {
+                Ok(()) => { self.watcher = Some(polled); },
+                Err(e) => {
+                    log::error!("Fail to watch `{}`: {}", self.dir.display(), e);
+                    self.watcher = None;
+                },
+            }
+        }
+    }
+
+    /// Walk the directories among `rows[idxs]` in the background, unless their size is known.
+    fn size_dirs(&mut self, idxs: Vec<usize>) {
+        let mut paths = vec![];
+        for idx in idxs {
+            let rf = &mut self.list.rows[idx];
+            if rf.file.file_type == FileType::Dir && rf.size == DirSize::Unknown {
+                rf.size = DirSize::Pending;
+                paths.push(rf.file.path.clone());
+            }
+        }
+        if paths.is_empty() { return; }
+
+        let sizer = self.sizer.get_or_insert_with(|| Sizer::spawn(Box::new(self.background.clone())));
+        for path in paths {
+            self.sizing.insert(path.clone());
+            sizer.request(path);
+        }
+    }
+
+    /// Take the sizes walked in the background, call it regularly.
+    pub fn poll(&mut self) {
+        /// A burst of changes is refreshed once it calms down, or at this pace while it lasts.
+        const QUIET: Duration = Duration::from_millis(100);
+        const BUSY: Duration = Duration::from_secs(1);
+
+        if self.watcher.as_mut().is_some_and(|w| w.changed()) {
+            let now = Instant::now();
+            self.changes = Some((self.changes.map_or(now, |(first, _)| first), now));
+        }
+        if let Some((first, last)) = self.changes {
+            if last.elapsed() >= QUIET || first.elapsed() >= BUSY {
+                self.refresh();
+            }
+        }
+        self.poll_sizes();
+    }
+
+    fn poll_sizes(&mut self) {
+        let sizer = match &self.sizer { Some(sizer) => sizer, None => return };
+        while let Ok((path, gen, res)) = sizer.rx.try_recv() {
+            if gen != sizer.generation.load(Ordering::SeqCst) { continue; }
+            self.sizing.remove(&path);
+            let rf = self.list.rows.iter_mut().find(|rf| rf.file.path == path);
+            match res {
+                Ok(tree) => {
+                    let modified = rf.as_ref().and_then(|rf| rf.file.metadata.modified);
+                    self.sizes.insert(path, (modified, tree));
+                    if let Some(rf) = rf { rf.size = DirSize::Done(tree); }
+                },
+                Err(e) => {
+                    log::error!("Fail to get the size of `{}`: {}", path.display(), e);
+                    if let Some(rf) = rf { rf.size = DirSize::Unknown; }
+                },
+            }
+        }
+    }
+
+    /// Rows a batch operation works on: the visual range, else the selection, else the row under the cursor.
+    fn targets(&self) -> Vec<usize> {
+        let cursor = self.list.cursor();
+        if let Some(anchor) = self.visual {
+            return (anchor.min(cursor)..=anchor.max(cursor)).collect();
+        }
+        let selected: Vec<_> = self.list.rows.iter().enumerate().filter(|(_, rf)| rf.selected).map(|(idx, _)| idx).collect();
+        if selected.is_empty() && cursor < self.list.rows.len() { vec![cursor] } else { selected }
+    }
+
+    fn in_visual(&self, idx: usize) -> bool {
+        match self.visual {
+            Some(anchor) => anchor.min(self.list.cursor()) <= idx && idx <= anchor.max(self.list.cursor()),
+            None => false,
+        }
+    }
+
+    /// Mark every target, which ends the visual mode and clears the selection.
+    fn set_mark(&mut self, mark: Mark) {
+        for idx in self.targets() {
+            let rf = &mut self.list.rows[idx];
+            if !rf.file.is_dot_dot() { rf.mark = mark; }
+        }
+        self.visual = None;
+        self.select_where(|_| false);
+    }
+
+    fn select_where(&mut self, mut f: impl FnMut(&RowFile) -> bool) {
+        for rf in self.list.rows.iter_mut() {
+            rf.selected = !rf.file.is_dot_dot() && f(rf);
+        }
+    }
+
+    /// Start a visual range, or end it and add it to the selection.
+    fn toggle_visual(&mut self) {
+        if self.visual.is_none() {
+            self.visual = Some(self.list.cursor());
+            return;
+        }
+        for idx in self.targets() {
+            let rf = &mut self.list.rows[idx];
+            if !rf.file.is_dot_dot() { rf.selected = true; }
+        }
+        self.visual = None;
+    }
+
+    /// Move the cursor to the row of `path`, false if it is not listed.
+    pub fn go_path(&mut self, path: &Path) -> bool {
+        match self.list.rows.iter().position(|rf| rf.file.path == path) {
+            Some(idx) => { self.list.go_index(idx); true },
+            None => false,
+        }
+    }
+
+    /// Create `name` under the current directory, missing directories on the way are created too
+    /// but the last part never replaces an existing entry. The cursor goes to the new entry of this directory.
+    pub fn create(&mut self, name: &str, is_dir: bool) -> io::Result<()> {
+        // stay inside this directory
+        if Path::new(name).has_root() || name.split(std::path::is_separator).any(|part| part == "." || part == "..") {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("`{}` is not a valid name", name)));
+        }
+        let path = self.dir.join(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(&mut self.host, parent)?;
+        }
+        if is_dir {
+            self.host.create_dir(&path)?;
+        } else {
+            self.host.create_file(&path)?;
+        }
+
+        self._refresh()?;
+        if let Some(first) = path.strip_prefix(&self.dir).ok().and_then(|p| p.components().next()) {
+            self.go_path(&self.dir.join(first));
+        }
+        Ok(())
+    }
+
+    /// Turn every marked row into a job and clear its mark, `target` is where uploads go.
+    pub fn take_jobs(&mut self, target: &Path) -> Vec<Job> {
+        let mut jobs = vec![];
+        for rf in self.list.rows.iter_mut() {
+            if rf.file.is_dot_dot() { continue; }
+            match std::mem::replace(&mut rf.mark, Mark::None) {
+                Mark::Upload => jobs.push(Job::Copy { src: rf.file.path.clone(), dst: target.join(&rf.file.name) }),
+                Mark::Move   => jobs.push(Job::Move { src: rf.file.path.clone(), dst: target.join(&rf.file.name) }),
+                Mark::Delete => jobs.push(Job::Delete(rf.file.path.clone())),
+                Mark::None   => { },
+            }
+        }
+        jobs
+    }
 }
 
 impl<H: Host> Ui for ExplorerPanel<H> {
@@ -223,37 +589,70 @@ impl<H: Host> Ui for ExplorerPanel<H> {
         self.list.suit(Rect { y: r_table.y.saturating_add(1), height: r_table.height.saturating_sub(1), .. r_table });
         let mut rows = vec![];
 
-        const HEADER: [&'static str; 7] = ["", "name", "size", "modified", "permissions", "type", "action", ];
-        let mut width: Vec<_> = HEADER.iter().map(|c| c.len() + 1).collect();
+        // the cursor is always in the first column
+        let header: Vec<_> = std::iter::once("").chain(self.columns.0.iter().map(|c| c.column.title())).collect();
+        let mut width: Vec<_> = header.iter().map(|c| c.len() + 1).collect();
         let (rfs, select) = self.list.view();
         for (idx, rf) in rfs.iter().enumerate() {
-            let row = rf.to_row(theme, idx == select);
+            let row = rf.to_row(theme, idx == select, &self.columns);
             for (col, width) in row.iter().zip(width.iter_mut()) {
                 *width = (*width).max(col.width())
             }
 
-            rows.push(Row::new(row));
+            let picked = rf.selected || self.in_visual(self.list.offset + idx);
+            rows.push(Row::new(row).style(if picked { theme.selected() } else { Style::default() }));
+        }
+
+        let fixed = std::iter::once(None).chain(self.columns.0.iter().map(|c| c.width));
+        for (width, fixed) in width.iter_mut().zip(fixed) {
+            if let Some(fixed) = fixed { *width = fixed as usize; }
         }
 
         f.render_widget(
             Table::new(rows).header(
-                Row::new(HEADER)).widths(&width.into_iter().map(|w| Constraint::Length(w as u16)).collect::<Vec<_>>()),
+                Row::new(header)).widths(&width.into_iter().map(|w| Constraint::Length(w as u16)).collect::<Vec<_>>()),
             r_table,
         );
 
         f.render_widget(
             Paragraph::new(Spans::from(vec![
                 Span::raw(" "),
-                Span::styled(format!("{}", self.dir.display()), Style::default().fg(Color::Red)),
+                Span::styled(match self.host.remote() {
+                    Some(remote) => format!("{}:{}", remote, self.dir.display()),
+                    None         => format!("{}", self.dir.display()),
+                }, theme.error()),
             ])).alignment(Alignment::Left),
             r_dir,
         );
 
         f.render_widget(
             Paragraph::new(Spans::from(vec![
-                Span::styled("Hidden", if self.show_hidden { Style::default().bg(Color::Red) } else { Style::default() } ),
+                match &self.search {
+                    Some(search) => Span::styled(match &search.error {
+                        Some(_) => format!("{}{} (bad) ", search.mode.prefix(), search.pattern),
+                        None    => format!("{}{} ({}) ", search.mode.prefix(), search.pattern,
+                            self.list.rows.iter().filter(|rf| !rf.matched.is_empty()).count()),
+                    }, theme.highlight()),
+                    None => Span::raw(""),
+                },
+                Span::raw(" "),
+                Span::styled(match (self.visual, self.list.rows.iter().filter(|rf| rf.selected).count()) {
+                    (Some(_), _) => "VISUAL ".to_string(),
+                    (None, 0)    => String::new(),
+                    (None, n)    => format!("{} selected ", n),
+                }, theme.selected()),
+                Span::styled("Hidden", if self.show_hidden { theme.flag() } else { Style::default() } ),
+                Span::raw(" "),
+                match &self.filter {
+                    Some(filter) => Span::styled(format!("Filter: {}", filter), theme.flag()),
+                    None         => Span::raw("Filter"),
+                },
+                Span::raw(" "),
+                Span::styled("Size", if self.auto_size { theme.flag() } else { Style::default() } ),
+                Span::raw(" "),
+                Span::styled("Dirs", if self.dirs_first { theme.flag() } else { Style::default() } ),
                 Span::raw(" "),
-                Span::raw("Sort: name"),
+                Span::raw(format!("Sort: {}{}", self.sort_by.name(), if self.sort_reverse { " (rev)" } else { "" })),
                 Span::raw(" "),
             ])).alignment(Alignment::Right),
             r_opt,
@@ -262,21 +661,22 @@ impl<H: Host> Ui for ExplorerPanel<H> {
 
     fn on_event(&mut self, event: Event) {
         match event {
-            Event::Key(KeyEvent { code: KeyCode::Up | KeyCode::Char('k'), modifiers: KeyModifiers::NONE }) => {
+            Event::Action(Action::Up) => {
                 self.list.go_select_up();
             },
-            Event::Key(KeyEvent { code: KeyCode::Down | KeyCode::Char('j'), modifiers: KeyModifiers::NONE }) => {
+            Event::Action(Action::Down) => {
                 self.list.go_select_down();
             },
-            Event::Key(KeyEvent { code: KeyCode::Char('.'), modifiers: KeyModifiers::NONE }) => {
+            Event::Action(Action::Cwd) => {
                 self.dir = PathBuf::from(".");
                 self.refresh();
                 self.list.go_select_down();
             },
-            Event::Key(KeyEvent { code: KeyCode::Enter, modifiers: KeyModifiers::NONE }) => {
+            Event::Action(Action::Open) => {
                 let (rfs, select) = self.list.view();
                 if select >= rfs.len() { return; }
                 let file_type = rfs[select].file.file_type;
+                let broken = rfs[select].file.broken;
                 let file_path = rfs[select].file.path.clone();
 
                 match file_type {
@@ -284,64 +684,115 @@ impl<H: Host> Ui for ExplorerPanel<H> {
                         self.dir = file_path;
                         self.refresh();
                     },
+                    // only links to directories can be entered, the listing shows where they lead
+                    FileType::SymLink if !broken => {
+                        match self.host.canonicalize(&file_path).and_then(|target| self.host.stat(&target)) {
+                            Ok(target) if target.is_dir() => {
+                                self.dir = target.path;
+                                self.refresh();
+                            },
+                            Ok(_) => { },
+                            Err(e) => { log::error!("Fail to follow `{}`: {}", file_path.display(), e); },
+                        }
+                    },
                     _ => {}
                 }
             },
-            Event::Key(KeyEvent { code: KeyCode::Backspace, modifiers: KeyModifiers::NONE }) => {
+            Event::Action(Action::Parent) => {
                 self.dir = self.dir.join("..").clone(); self.refresh();
             },
-            Event::Key(KeyEvent { code: KeyCode::Char('u'), modifiers: KeyModifiers::CONTROL }) => {
+            Event::Action(Action::PageUp) => {
                 self.list.go_scroll_up_page();
             },
-            Event::Key(KeyEvent { code: KeyCode::Char('d'), modifiers: KeyModifiers::CONTROL }) => {
+            Event::Action(Action::PageDown) => {
                 self.list.go_scroll_down_page();
             },
-            Event::Key(KeyEvent { code: KeyCode::Char('x'), modifiers: KeyModifiers::NONE }) => {
-                log::info!("Executing tasks...");
+            Event::Action(Action::MarkUpload) => {
+                self.set_mark(Mark::Upload);
+            },
+            Event::Action(Action::MarkMove) => {
+                self.set_mark(Mark::Move);
+            },
+            Event::Action(Action::MarkDelete) => {
+                self.set_mark(Mark::Delete);
             },
-            Event::Key(KeyEvent { code: KeyCode::Char('U'), modifiers: KeyModifiers::SHIFT }) => {
-                let rf = &mut self.list.rows[self.list.offset.saturating_add(self.list.select)];
-                rf.mark = Mark::Upload;
+            Event::Action(Action::MarkClear) => {
+                self.set_mark(Mark::None);
             },
-            Event::Key(KeyEvent { code: KeyCode::Char('D'), modifiers: KeyModifiers::SHIFT }) => {
-                let rf = &mut self.list.rows[self.list.offset.saturating_add(self.list.select)];
-                rf.mark = Mark::Delete;
+
+            // selection
+            Event::Action(Action::Visual) => {
+                self.toggle_visual();
+            },
+
+            // size of directories
+            Event::Action(Action::Size) => {
+                let targets = self.targets();
+                self.size_dirs(targets);
+            },
+            Event::Action(Action::AutoSize) => {
+                self.auto_size = !self.auto_size;
+                if self.auto_size { self.size_dirs((0..self.list.rows.len()).collect()); }
+            },
+            // sort
+            Event::Action(Action::Sort) => {
+                self.sort_by = self.sort_by.next();
+                self.refresh();
+            },
+            Event::Action(Action::SortReverse) => {
+                self.sort_reverse = !self.sort_reverse;
+                self.refresh();
+            },
+            Event::Action(Action::DirsFirst) => {
+                self.dirs_first = !self.dirs_first;
+                self.refresh();
             },
-            Event::Key(KeyEvent { code: KeyCode::Char('C'), modifiers: KeyModifiers::SHIFT }) => {
-                let rf = &mut self.list.rows[self.list.offset.saturating_add(self.list.select)];
-                rf.mark = Mark::None;
+            Event::Action(Action::Long) => {
+                self.columns.toggle_long();
+            },
+            Event::Action(Action::Select) => {
+                let cursor = self.list.cursor();
+                if let Some(rf) = self.list.rows.get_mut(cursor) {
+                    rf.selected = !rf.selected && !rf.file.is_dot_dot();
+                }
+                self.list.go_select_down();
+            },
+            Event::Action(Action::SelectAll) => {
+                let all = self.list.rows.iter().all(|rf| rf.selected || rf.file.is_dot_dot());
+                self.select_where(|_| !all);
+            },
+            Event::Action(Action::SelectInvert) => {
+                self.select_where(|rf| !rf.selected);
+            },
+            Event::SelectGlob(pattern) => {
+                self.select_where(|rf| rf.selected || glob_match(&pattern, &rf.file.name));
+            },
+            Event::DeselectGlob(pattern) => {
+                self.select_where(|rf| rf.selected && !glob_match(&pattern, &rf.file.name));
             },
             Event::ScrollDown => { self.list.go_scroll_down(); },
             Event::ScrollUp => { self.list.go_scroll_up(); },
             Event::Click(col, row) => {
                 self.list.go_select((col, row));
             },
-            Event::Keys_gg => { self.list.go_top(); }
-            Event::Keys_G  => { self.list.go_bottom(); },
-            Event::Search(search) => {
-                for rf in self.list.rows.iter_mut() {
-                    if let Some((idx, _)) = rf.file.name.match_indices(&search).next() {
-                        rf.matched = Some((idx, idx+search.len()));
-                    } else {
-                        rf.matched = None;
-                    }
-                }
+            Event::Action(Action::Top) => { self.list.go_top(); }
+            Event::Action(Action::Bottom) => { self.list.go_bottom(); },
+            Event::Search(pattern, mode) => {
+                self.search(&pattern, mode);
             },
-            Event::Key(KeyEvent { code: KeyCode::Char('l'), modifiers: KeyModifiers::CONTROL }) => {
-                for rf in self.list.rows.iter_mut() {
-                    rf.matched = None;
-                }
+            Event::Action(Action::SearchClear) => {
+                self.search("", SearchMode::Substring);
             },
 
             // TODO: need better search movement
-            Event::Key(KeyEvent { code: KeyCode::Char('n'), modifiers: KeyModifiers::NONE }) => {
+            Event::Action(Action::SearchNext) => {
                 let offset_old = self.list.offset; let select_old = self.list.select;
                 let mut restore = true;
                 loop {
                     self.list.go_select_down();
                     let (view, idx) = self.list.view();
                     if idx == 0 || idx + 1 >= view.len(){ break; }
-                    if view[idx].matched.is_some()  { restore = false; break; }
+                    if !view[idx].matched.is_empty() { restore = false; break; }
                 }
 
                 if restore {
@@ -349,14 +800,14 @@ impl<H: Host> Ui for ExplorerPanel<H> {
                     self.list.select = select_old;
                 }
             },
-            Event::Key(KeyEvent { code: KeyCode::Char('N'), modifiers: KeyModifiers::SHIFT }) => {
+            Event::Action(Action::SearchPrev) => {
                 let offset_old = self.list.offset; let select_old = self.list.select;
                 let mut restore = true;
                 loop {
                     self.list.go_select_up();
                     let (view, idx) = self.list.view();
                     if idx == 0 || idx + 1 >= view.len(){ break; }
-                    if view[idx].matched.is_some()  { restore = false; break; }
+                    if !view[idx].matched.is_empty() { restore = false; break; }
                 }
 
                 if restore {
@@ -375,12 +826,22 @@ pub enum Mark {
     None,
     Delete,
     Upload,
+    Move,
 }
 
 pub struct RowFile {
     file: File,
-    matched: Option<(usize, usize)>,
-    mark: Mark
+    matched: Vec<(usize, usize)>,
+    mark: Mark,
+    selected: bool,
+    size: DirSize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DirSize {
+    Unknown,
+    Pending,
+    Done(TreeSize),
 }
 
 impl Mark {
@@ -388,36 +849,480 @@ impl Mark {
         match self {
             Self::Delete => "Delete",
             Self::Upload => "Upload",
+            Self::Move   => "Move",
             Self::None   => "",
         }
     }
 }
 
 impl RowFile {
-    fn to_row<S: FeStyles>(&self, style: &S, select: bool) -> [Spans<'_>; 7] {
-        let File { name, file_type, metadata, ext, .. } = &self.file;
+    fn to_row<S: FeStyles>(&self, style: &S, select: bool, columns: &Columns) -> Vec<Spans<'_>> {
+        std::iter::once(Spans::from(if select { Span::styled(" ", style.cursor_select()) } else { Span::raw(" ") }))
+            .chain(columns.0.iter().map(|c| c.column.render(self, style)))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SearchMode {
+    Substring,
+    Regex,
+    /// The whole name matches, as the select globs do.
+    Glob,
+}
+
+impl SearchMode {
+    pub fn next(&self) -> Self {
+        match self {
+            Self::Substring => Self::Regex,
+            Self::Regex     => Self::Glob,
+            Self::Glob      => Self::Substring,
+        }
+    }
+
+    /// Shown before the pattern.
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            Self::Substring => "/",
+            Self::Regex     => "re/",
+            Self::Glob      => "glob/",
+        }
+    }
+}
+
+/// Names are searched ignoring the case, unless the pattern has upper case.
+#[derive(Debug)]
+struct Search {
+    pattern: String,
+    mode: SearchMode,
+    ignore_case: bool,
+    regex: Option<Regex>,
+    error: Option<String>, // of a regex being typed, nothing matches
+}
+
+impl Search {
+    fn new(pattern: &str, mode: SearchMode) -> Self {
+        let ignore_case = !pattern.chars().any(char::is_uppercase);
+        let (regex, error) = match mode {
+            SearchMode::Regex => match RegexBuilder::new(pattern).case_insensitive(ignore_case).build() {
+                Ok(regex) => (Some(regex), None),
+                Err(e)    => (None, Some(e.to_string())),
+            },
+            _ => (None, None),
+        };
+        Self { pattern: pattern.to_owned(), mode, ignore_case, regex, error }
+    }
+
+    /// Byte ranges of every match in `name`, widened to whole graphemes.
+    fn find(&self, name: &str) -> Vec<(usize, usize)> {
+        let ranges: Vec<(usize, usize)> = match self.mode {
+            SearchMode::Substring if self.ignore_case => {
+                // fold grapheme by grapheme and keep where each folded one comes from, lower case may change lengths
+                let mut folded = String::new();
+                let mut origin = vec![];
+                for (idx, g) in name.grapheme_indices(true) {
+                    let lower = g.to_lowercase();
+                    origin.extend(std::iter::repeat_n((idx, idx + g.len()), lower.len()));
+                    folded.push_str(&lower);
+                }
+                folded.match_indices(&self.pattern.to_lowercase())
+                    .filter(|(_, m)| !m.is_empty())
+                    .map(|(idx, m)| (origin[idx].0, origin[idx + m.len() - 1].1))
+                    .collect()
+            },
+            SearchMode::Substring => name.match_indices(&self.pattern).map(|(idx, m)| (idx, idx + m.len())).collect(),
+            SearchMode::Regex => match &self.regex {
+                Some(regex) => regex.find_iter(name).filter(|m| !m.as_str().is_empty()).map(|m| (m.start(), m.end())).collect(),
+                None => vec![],
+            },
+            SearchMode::Glob => {
+                let matched = if self.ignore_case {
+                    glob_match(&self.pattern.to_lowercase(), &name.to_lowercase())
+                } else {
+                    glob_match(&self.pattern, name)
+                };
+                if matched { vec![(0, name.len())] } else { vec![] }
+            },
+        };
+        align_graphemes(name, ranges)
+    }
+}
+
+/// Terms separated by spaces which all have to match: a substring of the name (a glob with `*?[`),
+/// `re:<regex>`, `size<op><n>[KMGT]` and `mtime<op><n><m|h|d|w>` for the age, with `op` one of `< <= > >= =`.
+#[derive(Debug)]
+pub struct Filter {
+    text: String,
+    terms: Vec<FilterTerm>,
+}
+
+#[derive(Debug)]
+enum FilterTerm {
+    Name(Search),
+    Size(std::cmp::Ordering, bool, u64), // the side of the bound, whether equal is fine
+    Age(std::cmp::Ordering, bool, chrono::Duration),
+}
+
+impl Filter {
+    pub fn matches(&self, file: &File) -> bool {
+        let cmp = |ord: std::cmp::Ordering, or_equal: bool, o: std::cmp::Ordering| o == ord || (or_equal && o.is_eq());
+        self.terms.iter().all(|term| match term {
+            FilterTerm::Name(search) => !search.find(&file.name).is_empty(),
+            FilterTerm::Size(ord, eq, n) => cmp(*ord, *eq, file.metadata.len.cmp(n)),
+            FilterTerm::Age(ord, eq, age) => match file.metadata.modified {
+                Some(modified) => cmp(*ord, *eq, (Local::now() - modified).cmp(age)),
+                None => false,
+            },
+        })
+    }
+}
+
+impl std::str::FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut terms = vec![];
+        for word in s.split_whitespace() {
+            let term = if let Some(pattern) = word.strip_prefix("re:") {
+                let search = Search::new(pattern, SearchMode::Regex);
+                if let Some(e) = search.error { return Err(e); }
+                FilterTerm::Name(search)
+            } else if let Some(rest) = word.strip_prefix("size") {
+                let (ord, eq, n) = parse_bound(rest).ok_or_else(|| format!("bad size `{}`", word))?;
+                let (n, unit) = n.split_at(n.trim_end_matches(|c: char| c.is_ascii_alphabetic()).len());
+                let shift = match unit.to_ascii_uppercase().trim_end_matches('B') {
+                    ""  => 0,
+                    "K" => 10,
+                    "M" => 20,
+                    "G" => 30,
+                    "T" => 40,
+                    _   => return Err(format!("bad size unit in `{}`", word)),
+                };
+                let n: u64 = n.parse().map_err(|_| format!("bad size `{}`", word))?;
+                FilterTerm::Size(ord, eq, n << shift)
+            } else if let Some(rest) = word.strip_prefix("mtime") {
+                let (ord, eq, n) = parse_bound(rest).ok_or_else(|| format!("bad age `{}`", word))?;
+                let (n, unit) = n.split_at(n.len().saturating_sub(1));
+                let n: i64 = n.parse().map_err(|_| format!("bad age `{}`", word))?;
+                let age = match unit {
+                    "m" => chrono::Duration::minutes(n),
+                    "h" => chrono::Duration::hours(n),
+                    "d" => chrono::Duration::days(n),
+                    "w" => chrono::Duration::weeks(n),
+                    _   => return Err(format!("bad age unit in `{}`, one of m h d w", word)),
+                };
+                FilterTerm::Age(ord, eq, age)
+            } else if word.contains(['*', '?', '[']) {
+                FilterTerm::Name(Search::new(word, SearchMode::Glob))
+            } else {
+                FilterTerm::Name(Search::new(word, SearchMode::Substring))
+            };
+            terms.push(term);
+        }
+        Ok(Self { text: s.trim().to_owned(), terms })
+    }
+}
+
+impl std::fmt::Display for Filter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+/// `>=10` into the side of the bound, whether equal is fine and `10`.
+fn parse_bound(s: &str) -> Option<(std::cmp::Ordering, bool, &str)> {
+    use std::cmp::Ordering;
+    let bound = [(">=", Ordering::Greater, true), ("<=", Ordering::Less, true), (">", Ordering::Greater, false),
+                 ("<", Ordering::Less, false), ("=", Ordering::Equal, true)];
+    bound.into_iter().find_map(|(op, ord, eq)| s.strip_prefix(op).map(|rest| (ord, eq, rest)))
+        .filter(|(_, _, rest)| !rest.is_empty())
+}
+
+/// Widen sorted `ranges` of `text` to grapheme boundaries, the ones touching are merged.
+fn align_graphemes(text: &str, ranges: Vec<(usize, usize)>) -> Vec<(usize, usize)> {
+    if ranges.is_empty() { return ranges; }
+    let bounds: Vec<usize> = text.grapheme_indices(true).map(|(idx, _)| idx).chain(std::iter::once(text.len())).collect();
+    let mut out: Vec<(usize, usize)> = vec![];
+    for (start, end) in ranges {
+        let start = bounds[bounds.partition_point(|&b| b <= start) - 1];
+        let end = bounds[bounds.partition_point(|&b| b < end).min(bounds.len() - 1)];
+        match out.last_mut() {
+            Some(last) if last.1 >= start => { last.1 = last.1.max(end); },
+            _ => { out.push((start, end)); },
+        }
+    }
+    out
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    /// Byte order of the names.
+    Name,
+    /// Ignore the case and compare numbers, `file2` before `file10`.
+    Natural,
+    Extension,
+    Size,
+    Modified,
+    Accessed,
+    Created,
+    /// Kind of file, then the extension.
+    Type,
+}
+
+impl SortKey {
+    pub const ALL: [SortKey; 8] = [
+        Self::Natural, Self::Name, Self::Extension, Self::Size, Self::Modified, Self::Accessed, Self::Created, Self::Type,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Name      => "name",
+            Self::Natural   => "natural",
+            Self::Extension => "ext",
+            Self::Size      => "size",
+            Self::Modified  => "modified",
+            Self::Accessed  => "accessed",
+            Self::Created   => "created",
+            Self::Type      => "type",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        let idx = Self::ALL.iter().position(|k| k == self).unwrap_or(0);
+        Self::ALL[(idx + 1) % Self::ALL.len()]
+    }
+
+    /// Ties are left to the caller.
+    fn cmp(&self, f1: &File, f2: &File) -> std::cmp::Ordering {
+        let (m1, m2) = (&f1.metadata, &f2.metadata);
+        match self {
+            Self::Name      => f1.name.cmp(&f2.name),
+            Self::Natural   => natural_cmp(&f1.name, &f2.name),
+            Self::Extension => f1.ext.cmp(&f2.ext).then_with(|| natural_cmp(&f1.name, &f2.name)),
+            Self::Size      => m1.len.cmp(&m2.len),
+            Self::Modified  => m1.modified.cmp(&m2.modified),
+            Self::Accessed  => m1.accessed.cmp(&m2.accessed),
+            Self::Created   => m1.created.cmp(&m2.created),
+            Self::Type      => (f1.file_type, m1.special.map(|s| s as u8), &f1.ext).cmp(&(f2.file_type, m2.special.map(|s| s as u8), &f2.ext)),
+        }
+    }
+}
+
+impl std::fmt::Display for SortKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl std::str::FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL.into_iter().find(|k| k.name() == s).ok_or_else(|| format!("unknown sort `{}`", s))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+    Name,
+    Size,
+    Modified,
+    Accessed,
+    Created,
+    Permissions,
+    Owner,
+    Group,
+    Inode,
+    Links,
+    Blocks,
+    Type,
+    Extension,
+    Mark,
+}
+
+/// A column of the table, fit to its content without a width.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColumnSpec {
+    pub column: Column,
+    pub width: Option<u16>,
+}
+
+/// Columns of an explorer, written as `name:40,size,modified` in the config and the prompt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Columns(pub Vec<ColumnSpec>);
+
+impl Column {
+    pub const ALL: [Column; 14] = [
+        Self::Name, Self::Size, Self::Modified, Self::Accessed, Self::Created, Self::Permissions, Self::Owner,
+        Self::Group, Self::Inode, Self::Links, Self::Blocks, Self::Type, Self::Extension, Self::Mark,
+    ];
+    /// Shown or hidden together by `L`.
+    const LONG: [Column; 5] = [Self::Owner, Self::Group, Self::Inode, Self::Links, Self::Blocks];
+
+    /// Name in the config, also the header.
+    pub fn title(&self) -> &'static str {
+        match self {
+            Self::Name        => "name",
+            Self::Size        => "size",
+            Self::Modified    => "modified",
+            Self::Accessed    => "accessed",
+            Self::Created     => "created",
+            Self::Permissions => "permissions",
+            Self::Owner       => "owner",
+            Self::Group       => "group",
+            Self::Inode       => "inode",
+            Self::Links       => "links",
+            Self::Blocks      => "blocks",
+            Self::Type        => "type",
+            Self::Extension   => "ext",
+            Self::Mark        => "action",
+        }
+    }
+
+    fn render<'r, S: FeStyles>(&self, rf: &'r RowFile, style: &S) -> Spans<'r> {
+        let File { name, file_type, metadata, ext, link_target, broken, .. } = &rf.file;
         let file_type = *file_type;
-        let &Metadata { len, modified, permissions, .. } = metadata;
+        let &Metadata { len, modified, accessed, created, permissions, special, rdev, .. } = metadata;
+
+        match self {
+            Self::Name => Spans::from(render_name(name, &rf.matched, style, name_style(file_type, Indicator::of(&rf.file), name, style)).into_iter()
+                .chain(link_target.iter().flat_map(|target| render_link(target, *broken, style)))
+                .collect::<Vec<_>>()),
+            Self::Size => Spans::from(match rf.size {
+                DirSize::Done(tree) => render_tree_size(tree, style),
+                DirSize::Pending    => Span::styled("...", style.dashed()),
+                DirSize::Unknown    => render_size(len, style, file_type),
+            }),
+            Self::Modified    => Spans::from(render_datetime(modified, style)),
+            Self::Accessed    => Spans::from(render_datetime(accessed, style)),
+            Self::Created     => Spans::from(render_datetime(created, style)),
+            Self::Permissions => Spans::from(render_permission(permissions, style, file_type, special)),
+            Self::Owner       => Spans::from(render_owner(metadata, style)[0].clone()),
+            Self::Group       => Spans::from(render_owner(metadata, style)[1].clone()),
+            Self::Inode       => Spans::from(render_inode(metadata, style)[0].clone()),
+            Self::Links       => Spans::from(render_inode(metadata, style)[1].clone()),
+            Self::Blocks      => Spans::from(render_inode(metadata, style)[2].clone()),
+            Self::Type        => Spans::from(render_type(file_type, ext.as_ref(), style, special, rdev)),
+            Self::Extension   => Spans::from(render_ext(file_type, ext.as_ref())),
+            Self::Mark        => Spans::from(Span::styled(rf.mark.symbol(), style.mark())),
+        }
+    }
+}
+
+impl std::str::FromStr for Column {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "user"    => Ok(Self::Owner),
+            "mark"    => Ok(Self::Mark),
+            "mode"    => Ok(Self::Permissions),
+            _ => Self::ALL.into_iter().find(|c| c.title() == s).ok_or_else(|| format!("unknown column `{}`", s)),
+        }
+    }
+}
 
-        [
-            Spans::from(if select { Span::styled(" ", style.cursor_select()) } else { Span::raw(" ") }),
-            Spans::from(render_name(name, self.matched, style, file_type)),
-            Spans::from(render_size(len, style, file_type)),
-            Spans::from(render_datetime(modified, style)),
-            Spans::from(render_permission(permissions, style, file_type)),
-            Spans::from(render_type(file_type, ext.as_ref(), style)),
-            Spans::from(Span::styled(self.mark.symbol(), style.mark())),
-        ]
+impl Default for Columns {
+    fn default() -> Self {
+        Self([Column::Name, Column::Size, Column::Modified, Column::Permissions, Column::Type, Column::Mark]
+            .into_iter().map(|column| ColumnSpec { column, width: None }).collect())
     }
 }
 
-pub fn render_type<'n, S: FeStyles>(file_type: FileType, ext: Option<&'n String>, style: &S) -> Span<'n> {
-    if file_type == FileType::File {
-        if let Some(ext) = ext {
-            return Span::raw(ext);
+impl Columns {
+    /// Hide the owner, group, inode, links and blocks if any is shown, or show them after the permissions.
+    pub fn toggle_long(&mut self) {
+        if self.0.iter().any(|c| Column::LONG.contains(&c.column)) {
+            self.0.retain(|c| !Column::LONG.contains(&c.column));
+        } else {
+            let at = self.0.iter().position(|c| c.column == Column::Permissions).map_or(self.0.len(), |i| i + 1);
+            self.0.splice(at..at, Column::LONG.into_iter().map(|column| ColumnSpec { column, width: None }));
+        }
+    }
+}
+
+impl std::str::FromStr for Columns {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut columns = vec![];
+        for item in s.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+            let (name, width) = match item.split_once(':') {
+                Some((name, width)) => (name.trim(), Some(width.trim().parse::<u16>().map_err(|_| format!("bad width `{}` of `{}`", width, name))?)),
+                None                => (item, None),
+            };
+            let column = name.parse()?;
+            if columns.iter().any(|c: &ColumnSpec| c.column == column) {
+                return Err(format!("column `{}` is repeated", name));
+            }
+            columns.push(ColumnSpec { column, width });
+        }
+        Ok(Self(columns))
+    }
+}
+
+impl std::fmt::Display for Columns {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (idx, c) in self.0.iter().enumerate() {
+            if idx > 0 { write!(f, ",")?; }
+            write!(f, "{}", c.column.title())?;
+            if let Some(width) = c.width { write!(f, ":{}", width)?; }
         }
+        Ok(())
+    }
+}
+
+pub fn render_ext(file_type: FileType, ext: Option<&String>) -> Span<'_> {
+    match (file_type, ext) {
+        (FileType::File, Some(ext)) => Span::raw(ext),
+        _                           => Span::raw(""),
     }
-    Span::raw("")
+}
+
+pub fn render_type<'n, S: FeStyles>(file_type: FileType, ext: Option<&'n String>, style: &S, special: Option<Special>, rdev: Option<(u32, u32)>) -> Span<'n> {
+    match file_type {
+        FileType::File => render_ext(file_type, ext),
+        FileType::Other => {
+            let kind = match special {
+                Some(Special::BlockDevice) => "block",
+                Some(Special::CharDevice)  => "char",
+                Some(Special::Fifo)        => "fifo",
+                Some(Special::Socket)      => "socket",
+                None                       => "?",
+            };
+            match rdev {
+                Some((major, minor)) => Span::styled(format!("{} {},{}", kind, major, minor), style.other()),
+                None                 => Span::styled(kind, style.other()),
+            }
+        },
+        _ => Span::raw(""),
+    }
+}
+
+/// User and group, numeric ids when the names are unknown.
+pub fn render_owner<S: FeStyles>(metadata: &Metadata, style: &S) -> [Span<'static>; 2] {
+    let name = |name: &Option<String>, id: Option<u32>| match (name, id) {
+        (Some(name), _)   => Span::raw(name.clone()),
+        (None, Some(id))  => Span::raw(id.to_string()),
+        (None, None)      => Span::styled("-", style.dashed()),
+    };
+    [ name(&metadata.user, metadata.uid), name(&metadata.group, metadata.gid) ]
+}
+
+/// Inode, hard links and disk usage.
+pub fn render_inode<S: FeStyles>(metadata: &Metadata, style: &S) -> [Span<'static>; 3] {
+    let num = |n: Option<u64>| match n {
+        Some(n) => Span::raw(n.to_string()),
+        None    => Span::styled("-", style.dashed()),
+    };
+    [
+        num(metadata.inode),
+        num(metadata.nlink),
+        match metadata.blocks {
+            Some(blocks) => Span::styled(format_size(blocks), style.size()),
+            None         => Span::styled("-", style.dashed()),
+        },
+    ]
 }
 
 pub fn render_datetime<S: FeStyles>(dt: Option<chrono::DateTime<chrono::Local>>, style: &S) -> Span<'static> {
@@ -431,63 +1336,245 @@ pub fn render_datetime<S: FeStyles>(dt: Option<chrono::DateTime<chrono::Local>>,
     }
 }
 
-pub fn render_name<'n, S: FeStyles>(name: &'n str, matched: Option<(usize, usize)>, style: &S, file_type: FileType) -> Vec<Span<'n>> {
-    let normal = match file_type {
+/// From `LS_COLORS` when it knows the file, else by the type.
+pub fn name_style<S: FeStyles>(file_type: FileType, indicator: Indicator, name: &str, style: &S) -> Style {
+    if let Some(s) = style.ls_colors().style(indicator, name) { return s; }
+    match file_type {
         FileType::DotDot  => style.dot_dot(),
         FileType::Dir     => style.directory(),
         FileType::File    => style.file(),
         FileType::Other   => style.other(),
         FileType::SymLink => style.sym_link(),
-    };
-
-    if let Some((sidx, eidx)) = matched {
-        vec![
-            Span::styled(&name[..sidx], normal),
-            Span::styled(&name[sidx..eidx], style.highlight()),
-            Span::styled(&name[eidx..], normal),
-        ]
-    } else {
-        vec![ Span::styled(name, normal) ]
+    }
+}
 
+/// `name` in `normal`, with the `matched` parts highlighted.
+pub fn render_name<'n, S: FeStyles>(name: &'n str, matched: &[(usize, usize)], style: &S, normal: Style) -> Vec<Span<'n>> {
+    // `matched` are sorted byte ranges which don't overlap
+    let mut spans = vec![];
+    let mut at = 0;
+    for &(sidx, eidx) in matched {
+        if sidx > at { spans.push(Span::styled(&name[at..sidx], normal)); }
+        spans.push(Span::styled(&name[sidx..eidx], style.highlight()));
+        at = eidx;
     }
+    if at < name.len() || spans.is_empty() { spans.push(Span::styled(&name[at..], normal)); }
+    spans
+}
 
+/// ` -> target` after the name of a symlink.
+pub fn render_link<'n, S: FeStyles>(target: &'n Path, broken: bool, style: &S) -> [Span<'n>; 2] {
+    [
+        Span::styled(" -> ", style.dashed()),
+        Span::styled(target.to_string_lossy(), if broken {
+            style.ls_colors().style(Indicator::Missing, "").unwrap_or_else(|| style.broken_link())
+        } else {
+            style.sym_link()
+        }),
+    ]
 }
 
-pub fn render_permission<S: FeStyles>(p: Permissions, style: &S, file_type: FileType) -> Vec<Span<'static>> {
+pub fn render_permission<S: FeStyles>(p: Permissions, style: &S, file_type: FileType, special: Option<Special>) -> Vec<Span<'static>> {
     let bit = |b, c, s| if b { Span::styled(c, s) } else { Span::styled("-", style.dashed()) };
+    // setuid, setgid and sticky take the execute slot, upper case when it isn't executable
+    let special_bit = |b, x, c: &'static str, upper: &'static str, s| match (b, x) {
+        (true, true)  => Span::styled(c, style.special_bit()),
+        (true, false) => Span::styled(upper, style.special_bit()),
+        (false, _)    => bit(x, "x", s),
+    };
     let ft = match file_type {
         FileType::DotDot  => { return vec![]; },
         FileType::Dir     => Span::styled("d", style.directory()),
         FileType::File    => Span::styled(".", style.file()),
         FileType::SymLink => Span::styled("s", style.sym_link()),
-        FileType::Other   => Span::styled("?", style.other()),
+        FileType::Other   => Span::styled(match special {
+            Some(Special::BlockDevice) => "b",
+            Some(Special::CharDevice)  => "c",
+            Some(Special::Fifo)        => "|",
+            Some(Special::Socket)      => "=",
+            None                       => "?",
+        }, style.other()),
     };
 
     vec![
         ft,
         bit(p.user_read,     "r", style.user_read()),
         bit(p.user_write,    "w", style.user_write()),
-        bit(p.user_execute,  "x", style.user_execute()),
+        special_bit(p.setuid, p.user_execute, "s", "S", style.user_execute()),
         bit(p.group_read,    "r", style.group_read()),
         bit(p.group_write,   "w", style.group_write()),
-        bit(p.group_execute, "x", style.group_execute()),
+        special_bit(p.setgid, p.group_execute, "s", "S", style.group_execute()),
         bit(p.other_read,    "r", style.other_read()),
         bit(p.other_write,   "w", style.other_write()),
-        bit(p.other_execute, "x", style.other_execute()),
+        special_bit(p.sticky, p.other_execute, "t", "T", style.other_execute()),
     ]
 }
 
 pub fn render_size<S: FeStyles>(size: u64, style: &S, file_type: FileType) -> Span<'static> {
     match file_type {
-        FileType::File => {
-            let num = match NumberPrefix::binary(size as f64) {
-                NumberPrefix::Standalone(_)       => size.to_string(),
-                NumberPrefix::Prefixed(prefix, s) => format!("{:.0}{}", s, prefix.symbol())
-            };
-            Span::styled(num, style.size())
-        },
+        FileType::File => Span::styled(format_size(size), style.size()),
         FileType::DotDot => Span::raw(""),
         _ => Span::styled("-", style.dashed()),
     }
 }
 
+/// Size of a directory with the count of files below it.
+pub fn render_tree_size<S: FeStyles>(tree: TreeSize, style: &S) -> Span<'static> {
+    Span::styled(format!("{} ({})", format_size(tree.bytes), tree.files), style.size())
+}
+
+pub fn format_size(size: u64) -> String {
+    match NumberPrefix::binary(size as f64) {
+        NumberPrefix::Standalone(_)       => size.to_string(),
+        NumberPrefix::Prefixed(prefix, s) => format!("{:.0}{}", s, prefix.symbol())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::ScratchDir;
+    use crate::fs::LocalHost;
+
+    fn names(explorer: &ExplorerPanel<LocalHost>, idxs: Vec<usize>) -> Vec<String> {
+        idxs.into_iter().map(|idx| explorer.list.rows[idx].file.name.clone()).collect()
+    }
+
+    #[test]
+    fn visual_range_follows_its_files() {
+        let dir = ScratchDir::new("explorer", "visual");
+        for name in ["b", "c", "d"] { std::fs::write(dir.join(name), name).unwrap(); }
+        let mut explorer = ExplorerPanel::new(LocalHost, &dir, &PanelsConfig::default(), Columns::default()).unwrap();
+        let dir = explorer.dir().to_path_buf();
+
+        explorer.go_path(&dir.join("c"));
+        explorer.toggle_visual();
+        explorer.go_path(&dir.join("d"));
+        assert_eq!(names(&explorer, explorer.targets()), ["c", "d"]);
+
+        // rows shift under the range
+        std::fs::write(dir.join("a"), "a").unwrap();
+        explorer.refresh();
+        assert_eq!(names(&explorer, explorer.targets()), ["c", "d"]);
+
+        // its first file is gone
+        std::fs::remove_file(dir.join("c")).unwrap();
+        explorer.refresh();
+        assert_eq!(explorer.visual, None);
+        assert_eq!(names(&explorer, explorer.targets()), ["d"]);
+    }
+
+    #[test]
+    fn create_new_entries_with_their_parents() {
+        let dir = ScratchDir::new("explorer", "create");
+        std::fs::write(dir.join("kept"), "content").unwrap();
+        let mut explorer = ExplorerPanel::new(LocalHost, &dir, &PanelsConfig::default(), Columns::default()).unwrap();
+        let dir = explorer.dir().to_path_buf();
+
+        explorer.create("new", false).unwrap();
+        assert_eq!(explorer.current().unwrap().name, "new");
+        explorer.create("sub", true).unwrap();
+        assert!(dir.join("sub").is_dir());
+        // parents are created, existing ones are fine
+        explorer.create("sub/file", false).unwrap();
+        assert!(dir.join("sub/file").is_file());
+        assert_eq!(explorer.current().unwrap().name, "sub");
+        explorer.create("a/b/c", true).unwrap();
+        assert!(dir.join("a/b/c").is_dir());
+        assert_eq!(explorer.current().unwrap().name, "a");
+
+        // the last part is never replaced
+        for name in ["kept", "sub/file", "a/b"] {
+            assert!(explorer.create(name, false).is_err(), "{}", name);
+            assert!(explorer.create(name, true).is_err(), "{}", name);
+        }
+        assert_eq!(explorer.create("kept", false).unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(std::fs::read_to_string(dir.join("kept")).unwrap(), "content");
+        assert!(explorer.create("kept/file", false).is_err());
+
+        for name in [".", "..", "../file", "sub/../file", "sub/./file", "/tmp/file"] {
+            let e = explorer.create(name, false).unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::InvalidInput, "{}", name);
+        }
+        assert!(!dir.join("file").exists());
+    }
+
+    fn file(name: &str, len: u64, age: chrono::Duration) -> File {
+        File {
+            path: PathBuf::from("/").join(name),
+            name: name.to_owned(),
+            ext: name.rsplit_once('.').map(|(_, ext)| ext.to_owned()),
+            metadata: Metadata { len, modified: Some(Local::now() - age), ..Default::default() },
+            file_type: FileType::File,
+            link_target: None,
+            broken: false,
+        }
+    }
+
+    #[test]
+    fn filter_terms() {
+        let hour = chrono::Duration::hours(1);
+        let matches = |spec: &str, file: &File| spec.parse::<Filter>().unwrap().matches(file);
+        let notes = file("Notes.md", 2048, hour * 3);
+
+        assert!(matches("", &notes));
+        assert!(matches("note", &notes));
+        assert!(!matches("Note.", &notes));
+        assert!(matches("*.md", &notes));
+        assert!(!matches("*.rs", &notes));
+        assert!(matches("re:^n.*s\\.md$", &notes));
+        assert!(!matches("re:^N[a-z]+$", &notes));
+
+        assert!(matches("size>=2K", &notes));
+        assert!(matches("size=2048", &notes));
+        assert!(matches("size<2kb", &file("a", 2047, hour)));
+        assert!(!matches("size>2K", &notes));
+        assert!(matches("size>1M", &file("a", (1 << 20) + 1, hour)));
+        assert!(matches("mtime>2h", &notes));
+        assert!(!matches("mtime<2h", &notes));
+        assert!(matches("mtime<=1w", &notes));
+        // every term has to match
+        assert!(matches("md size>1K mtime<1d", &notes));
+        assert!(!matches("md size>1K mtime<1m", &notes));
+
+        let mut unknown = file("a", 0, hour);
+        unknown.metadata.modified = None;
+        assert!(!matches("mtime>1m", &unknown));
+
+        for bad in ["size>", "size~1", "size>1X", "size>-1", "mtime>3", "mtime>3y", "mtime>h", "re:(", "size1K"] {
+            assert!(bad.parse::<Filter>().is_err(), "{}", bad);
+        }
+        assert_eq!("  *.md  size>1K ".parse::<Filter>().unwrap().to_string(), "*.md  size>1K");
+    }
+
+    #[test]
+    fn columns_and_sort_keys() {
+        let columns: Columns = "name:40, size,mode,user:8".parse().unwrap();
+        assert_eq!(columns.0, [
+            ColumnSpec { column: Column::Name, width: Some(40) },
+            ColumnSpec { column: Column::Size, width: None },
+            ColumnSpec { column: Column::Permissions, width: None },
+            ColumnSpec { column: Column::Owner, width: Some(8) },
+        ]);
+        assert_eq!(columns.to_string(), "name:40,size,permissions,owner:8");
+        assert_eq!(columns.to_string().parse::<Columns>().unwrap(), columns);
+        assert_eq!(Columns::default().to_string().parse::<Columns>().unwrap(), Columns::default());
+        assert_eq!("".parse::<Columns>().unwrap(), Columns(vec![]));
+        for bad in ["name,size,name", "name:wide", "name:-1", "colour", "name:"] {
+            assert!(bad.parse::<Columns>().is_err(), "{}", bad);
+        }
+
+        let mut columns = Columns::default();
+        columns.toggle_long();
+        let at = columns.0.iter().position(|c| c.column == Column::Permissions).unwrap();
+        assert_eq!(columns.0[at + 1].column, Column::Owner);
+        columns.toggle_long();
+        assert_eq!(columns, Columns::default());
+
+        for key in SortKey::ALL {
+            assert_eq!(key.to_string().parse::<SortKey>(), Ok(key));
+        }
+        assert!("Name".parse::<SortKey>().is_err());
+        assert_eq!(SortKey::ALL.iter().fold(SortKey::Natural, |key, _| key.next()), SortKey::Natural);
+    }
+}
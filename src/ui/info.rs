@@ -1,10 +1,18 @@
 use super::*;
+use super::explorer::format_size;
+use super::keymap::Action;
 
-const TITLE_LOG: &'static str   = "LOG";
-const TITLE_QUEUE: &'static str = "Queue";
-const TITLE_OK: &'static str    = "Ok";
-const TITLE_ERR: &'static str   = "Err";
-const TITLES: [&'static str; 4] = [ TITLE_LOG, TITLE_QUEUE, TITLE_OK, TITLE_ERR ];
+use crate::job::{Control, Job, Progress, Side};
+use crate::widgets::Slider;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+const TITLE_LOG: &str   = "LOG";
+const TITLE_QUEUE: &str = "Queue";
+const TITLE_OK: &str    = "Ok";
+const TITLE_ERR: &str   = "Err";
+const TITLES: [&str; 4] = [ TITLE_LOG, TITLE_QUEUE, TITLE_OK, TITLE_ERR ];
 
 #[derive(Debug, Clone)]
 pub struct LogRecord {
@@ -20,8 +28,42 @@ pub struct LogPanel {
     rect: Rect,
 }
 
+pub enum JobState {
+    Pending,
+    Running(Running),
+    Ok { elapsed: Duration, bytes: u64 },
+    Err(String),
+}
+
+pub struct Running {
+    progress: Option<Progress>,
+    started: Instant,
+    sample: (Instant, u64),
+    rate: f64,
+}
+
+pub struct JobEntry {
+    id: usize,
+    side: Side,
+    job: Job,
+    desc: String,
+    control: Arc<Control>,
+    state: JobState,
+}
+
+/// All the jobs not finished yet, for the status bar.
+pub struct JobSummary {
+    pub running: usize,
+    pub pending: usize,
+    pub done: u64,
+    pub total: u64,
+    pub rate: f64,
+}
+
 pub struct InfoPanel {
     p_logs: LogPanel,
+    jobs: Vec<JobEntry>,
+    job_select: Option<usize>,  // id of the selected job, `None` follows the newest
 
     tab: usize,
     rect: Rect,
@@ -35,6 +77,8 @@ impl InfoPanel {
                 offset: 0,
                 rect: Rect::default(),
             },
+            jobs: vec![],
+            job_select: None,
 
             tab: 0,
             rect: Rect::default(),
@@ -42,30 +86,275 @@ impl InfoPanel {
     }
 }
 
-impl Ui for LogPanel {
-    fn draw<B: Backend>(&mut self, f: &mut Frame<B>, rect: Rect, _theme: &Theme) {
-        let mut items = vec![];
+impl Running {
+    fn new() -> Self {
+        let now = Instant::now();
+        Self { progress: None, started: now, sample: (now, 0), rate: 0.0 }
+    }
+
+    fn update(&mut self, progress: Progress) {
+        // smooth the speed over windows of half a second
+        let (at, done) = self.sample;
+        let dt = at.elapsed().as_secs_f64();
+        if dt >= 0.5 {
+            let rate = progress.done.saturating_sub(done) as f64 / dt;
+            self.rate = if self.rate > 0.0 { self.rate * 0.7 + rate * 0.3 } else { rate };
+            self.sample = (Instant::now(), progress.done);
+        }
+        self.progress = Some(progress);
+    }
+
+    fn done_total(&self) -> (u64, u64) {
+        self.progress.as_ref().map(|p| (p.done, p.total)).unwrap_or((0, 0))
+    }
+
+    fn eta(&self) -> Option<Duration> {
+        let (done, total) = self.done_total();
+        if self.rate > 0.0 && total >= done {
+            Some(Duration::from_secs_f64((total - done) as f64 / self.rate))
+        } else {
+            None
+        }
+    }
+}
+
+impl JobState {
+    fn height(&self) -> u16 {
+        match self {
+            Self::Pending | Self::Ok { .. } => 1,
+            Self::Err(_) => 2,
+            Self::Running(_) => 3,
+        }
+    }
+}
+
+impl InfoPanel {
+    fn on_job(&mut self, event: JobEvent) {
+        let id = event.id();
+        if let JobEvent::Queued { id, side, job, control } = event {
+            let desc = job.to_string();
+            self.jobs.push(JobEntry { id, side, job, desc, control, state: JobState::Pending });
+            return;
+        }
 
-        // ref: https://docs.rs/env_logger/latest/src/env_logger/fmt/writer/termcolor/extern_impl.rs.html
-        let s_level = |l| match l {
-            log::Level::Trace => Style::default().fg(Color::Cyan),
-            log::Level::Debug => Style::default().fg(Color::Blue),
-            log::Level::Info  => Style::default().fg(Color::Green),
-            log::Level::Warn  => Style::default().fg(Color::Yellow),
-            log::Level::Error => Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        let entry = match self.jobs.iter_mut().rev().find(|e| e.id == id) {
+            Some(entry) => entry,
+            None => { log::error!("Got event of unknown job #{}", id); return; }
         };
+        match (event, &mut entry.state) {
+            (JobEvent::Started(_), state) => {
+                *state = JobState::Running(Running::new());
+            },
+            (JobEvent::Progress(progress), JobState::Running(running)) => {
+                running.update(progress);
+            },
+            (JobEvent::Finished { error: None, .. }, state) => {
+                let (elapsed, bytes) = match state {
+                    JobState::Running(running) => (running.started.elapsed(), running.done_total().0),
+                    _ => (Duration::default(), 0),
+                };
+                *state = JobState::Ok { elapsed, bytes };
+            },
+            (JobEvent::Finished { error: Some(e), .. }, state) => {
+                *state = JobState::Err(e);
+            },
+            (event, _) => { log::error!("Unexpected event for job #{}: {:?}", id, event); },
+        }
+    }
+
+    /// Which jobs the current tab shows, `None` for the log tab.
+    fn job_filter(&self) -> Option<fn(&JobState) -> bool> {
+        match TITLES.get(self.tab).copied() {
+            Some(TITLE_QUEUE) => Some(|s| matches!(s, JobState::Pending | JobState::Running(_))),
+            Some(TITLE_OK)    => Some(|s| matches!(s, JobState::Ok { .. })),
+            Some(TITLE_ERR)   => Some(|s| matches!(s, JobState::Err(_))),
+            _                 => None,
+        }
+    }
+
+    /// Index in `jobs` of the selected job, none when it left the tab shown.
+    fn job_selected(&self) -> Option<usize> {
+        let filter = self.job_filter()?;
+        let mut shown = self.jobs.iter().enumerate().filter(|(_, e)| filter(&e.state));
+        match self.job_select {
+            Some(id) => shown.find(|(_, e)| e.id == id).map(|(idx, _)| idx),
+            None => shown.next_back().map(|(idx, _)| idx),
+        }
+    }
+
+    fn job_select_move(&mut self, down: bool) {
+        let filter = match self.job_filter() { Some(filter) => filter, None => return };
+        let shown: Vec<_> = self.jobs.iter().filter(|e| filter(&e.state)).map(|e| e.id).collect();
+        let last = match shown.len().checked_sub(1) { Some(last) => last, None => return };
+        // from the newest when the selected job is gone
+        let select = self.job_select.and_then(|id| shown.iter().position(|&i| i == id)).unwrap_or(last);
+        self.job_select = Some(shown[if down { (select + 1).min(last) } else { select.saturating_sub(1) }]);
+    }
+
+    fn job_toggle_pause(&mut self) {
+        if let Some(idx) = self.job_selected() {
+            let control = &self.jobs[idx].control;
+            if control.is_paused() { control.resume(); } else { control.pause(); }
+        }
+    }
+
+    fn job_cancel(&mut self) {
+        if let Some(idx) = self.job_selected() {
+            log::info!("Cancel job #{}", self.jobs[idx].id);
+            self.jobs[idx].control.cancel();
+        }
+    }
+
+    /// Take the selected failed job out of the Err tab, so it can be submitted again.
+    pub fn take_retry(&mut self) -> Option<(Side, Job)> {
+        if TITLES.get(self.tab) != Some(&TITLE_ERR) { return None; }
+        let idx = self.job_selected()?;
+        let entry = self.jobs.remove(idx);
+        Some((entry.side, entry.job))
+    }
+
+    /// Keys of the job tabs.
+    fn on_job_key(&mut self, event: Event) {
+        match event {
+            Event::Action(Action::Up) | Event::ScrollUp => {
+                self.job_select_move(false);
+            },
+            Event::Action(Action::Down) | Event::ScrollDown => {
+                self.job_select_move(true);
+            },
+            Event::Action(Action::Pause) => {
+                self.job_toggle_pause();
+            },
+            Event::Action(Action::CancelJob) => {
+                self.job_cancel();
+            },
+            _ => { },
+        }
+    }
+
+    pub fn job_summary(&self) -> Option<JobSummary> {
+        let mut summary = JobSummary { running: 0, pending: 0, done: 0, total: 0, rate: 0.0 };
+        for entry in self.jobs.iter() {
+            match &entry.state {
+                JobState::Pending => { summary.pending += 1; },
+                JobState::Running(running) => {
+                    let (done, total) = running.done_total();
+                    summary.running += 1;
+                    summary.done += done;
+                    summary.total += total;
+                    summary.rate += running.rate;
+                },
+                _ => { },
+            }
+        }
+        if summary.running + summary.pending > 0 { Some(summary) } else { None }
+    }
+
+    fn draw_jobs<B: Backend>(&self, f: &mut Frame<B>, rect: Rect, theme: &Theme, filter: fn(&JobState) -> bool) {
+        let entries: Vec<_> = self.jobs.iter().filter(|e| filter(&e.state)).collect();
+        let select = self.job_select.and_then(|id| entries.iter().position(|e| e.id == id));
+
+        // newest at the bottom, always in sight unless something above is selected
+        let mut height = 0;
+        let shown = entries.iter().rev().take_while(|e| { height += e.state.height(); height <= rect.height }).count();
+        let start = (entries.len() - shown).min(select.unwrap_or(usize::MAX));
+
+        let dim = theme.dim();
+        let mut y = rect.y;
+        for (idx, entry) in entries.iter().enumerate().skip(start) {
+            if y + entry.state.height() > rect.bottom() { break; }
+            let line = |y: u16| Rect { y, height: 1, .. rect };
+            let s_id = if select == Some(idx) { dim.add_modifier(Modifier::REVERSED) } else { dim };
+            let mut head = vec![
+                Span::styled(format!("#{:<4}", entry.id), s_id),
+                Span::raw(&*entry.desc),
+            ];
+
+            match &entry.state {
+                JobState::Pending => {
+                    let state = if entry.control.is_paused() { "  pending (paused)" } else { "  pending" };
+                    head.push(Span::styled(state, theme.warning()));
+                },
+                JobState::Ok { elapsed, bytes } => {
+                    head.push(Span::styled(format!("  {} in {}", format_size(*bytes), format_duration(*elapsed)), dim));
+                },
+                JobState::Err(e) => {
+                    f.render_widget(Paragraph::new(Span::styled(format!("     {}", e), theme.error())), line(y + 1));
+                },
+                JobState::Running(running) => {
+                    if entry.control.is_canceled() {
+                        head.push(Span::styled("  canceling", theme.error()));
+                    } else if entry.control.is_paused() {
+                        head.push(Span::styled("  paused", theme.warning()));
+                    }
+                    let (done, total) = running.done_total();
+                    let (file, file_done, file_total) = running.progress.as_ref()
+                        .map(|p| (p.file.display().to_string(), p.file_done, p.file_total))
+                        .unwrap_or_default();
+                    let eta = running.eta().map(format_duration).unwrap_or_else(|| "--:--".to_string());
+
+                    let stats = format!(
+                        "{:>3.0}% {}/{} {}/s ETA {} elapsed {}",
+                        ratio(done, total) * 100.0, format_size(done), format_size(total),
+                        format_size(running.rate as u64), eta, format_duration(running.started.elapsed()),
+                    );
+                    self.draw_bar(f, line(y + 1), theme, ratio(done, total), Span::raw(stats));
+                    self.draw_bar(f, line(y + 2), theme, ratio(file_done, file_total), Span::styled(file, dim));
+                },
+            }
+
+            f.render_widget(Paragraph::new(Spans::from(head)), line(y));
+            y += entry.state.height();
+        }
+    }
+
+    /// `[####    ] label` indented under the job title.
+    fn draw_bar<B: Backend>(&self, f: &mut Frame<B>, rect: Rect, theme: &Theme, ratio: f64, label: Span) {
+        const INDENT: u16 = 5;
+        let width = (rect.width / 3).min(30);
+        let r_bar = Rect { x: rect.x + INDENT, width: width.min(rect.width.saturating_sub(INDENT)), .. rect };
+        let r_label = Rect { x: r_bar.right() + 1, width: rect.right().saturating_sub(r_bar.right() + 1), .. rect };
+
+        f.render_widget(progress_bar(ratio, theme), r_bar);
+        f.render_widget(Paragraph::new(label), r_label);
+    }
+}
+
+pub fn progress_bar(ratio: f64, theme: &Theme) -> Slider {
+    let style = theme.progress();
+    Slider::filled(ratio)
+        .slider_color(style.fg.unwrap_or(Color::Reset))
+        .color(style.bg.unwrap_or(Color::Reset))
+}
+
+pub fn ratio(done: u64, total: u64) -> f64 {
+    if total == 0 { 1.0 } else { done as f64 / total as f64 }
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{:02}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+impl Ui for LogPanel {
+    fn draw<B: Backend>(&mut self, f: &mut Frame<B>, rect: Rect, theme: &Theme) {
+        let mut items = vec![];
 
         self.offset = self.offset.min(self.logs.len().saturating_sub(1));
         let mut height = 0;
         for record in self.logs.iter().skip(self.offset) {
             let mut spans = vec![Spans::from(vec![
-                Span::styled("[", Style::default().add_modifier(Modifier::DIM)),
+                Span::styled("[", theme.dim()),
                 Span::from(&*record.timestamp),
                 Span::from(" "),
-                Span::styled(format!("{: <5}", record.level), s_level(record.level)),
+                Span::styled(format!("{: <5}", record.level), theme.log(record.level)),
                 Span::from(" "),
                 Span::from(&*record.target),
-                Span::styled("]", Style::default().add_modifier(Modifier::DIM)),
+                Span::styled("]", theme.dim()),
                 Span::from(" "),
                 // Span::styled(&record.msg, Style::default().fg(Color::White)),
             ])];
@@ -96,13 +385,13 @@ impl Ui for LogPanel {
             Event::ScrollUp => {
                 self.offset = self.offset.saturating_sub(1);
             },
-            Event::Key(KeyEvent { code: KeyCode::Up | KeyCode::Char('k'), modifiers: KeyModifiers::NONE }) => {
+            Event::Action(Action::Up) => {
                 self.on_event(Event::ScrollUp);
             },
-            Event::Key(KeyEvent { code: KeyCode::Down | KeyCode::Char('j'), modifiers: KeyModifiers::NONE }) => {
+            Event::Action(Action::Down) => {
                 self.on_event(Event::ScrollDown);
             },
-            Event::Key(KeyEvent { code: KeyCode::Char('l'), modifiers: KeyModifiers::CONTROL }) => {
+            Event::Action(Action::Follow) => {
                 self.offset = self.logs.len().saturating_sub(1);
             },
             _ => { }
@@ -126,20 +415,16 @@ impl Ui for InfoPanel {
 
         f.render_widget(
             Tabs::new(titles)
-                .style(Style::default().fg(Color::White))
+                .style(theme.tabs())
                 .select(self.tab)
-                .highlight_style(Style::default().fg(Color::Yellow)),
+                .highlight_style(theme.tab_selected()),
             chunks[0]
         );
 
         if self.tab >= TITLES.len() || TITLES[self.tab] == TITLE_LOG {
             self.p_logs.draw(f, chunks[1], theme);
-        } else if TITLES[self.tab] == TITLE_QUEUE {
-            f.render_widget(Block::default().borders(Borders::ALL).title("Q"), chunks[1]);
-        } else if TITLES[self.tab] == TITLE_OK {
-            f.render_widget(Block::default().borders(Borders::ALL).title("O"), chunks[1]);
-        } else {
-            f.render_widget(Block::default().borders(Borders::ALL).title("E"), chunks[1]);
+        } else if let Some(filter) = self.job_filter() {
+            self.draw_jobs(f, chunks[1], theme, filter);
         }
 
         self.rect = rect;
@@ -148,6 +433,7 @@ impl Ui for InfoPanel {
     fn on_event(&mut self, event: Event) {
         match event {
             Event::Log(_)     => { self.p_logs.on_event(event); },
+            Event::Job(event) => { self.on_job(event); },
             Event::Click(column, row)  => {
                 if row == self.rect.y {
                     let mut left = self.rect.x;
@@ -162,17 +448,73 @@ impl Ui for InfoPanel {
                     }
                 }
             },
-            Event::Key(KeyEvent { code: KeyCode::Right | KeyCode::Char('l'), modifiers: KeyModifiers::NONE }) => {
+            Event::Action(Action::Right) => {
                 self.tab = self.tab.saturating_add(1).min(TITLES.len().saturating_sub(1));
+                self.job_select = None;
             },
-            Event::Key(KeyEvent { code: KeyCode::Left | KeyCode::Char('h'), modifiers: KeyModifiers::NONE }) => {
+            Event::Action(Action::Left) => {
                 self.tab = self.tab.saturating_sub(1).min(TITLES.len().saturating_sub(1));
+                self.job_select = None;
             },
-            _                 => {
-                if self.tab >= TITLES.len() || TITLES[self.tab] == TITLE_LOG {
-                    self.p_logs.on_event(event);
-                }
+            _ if self.job_filter().is_none() => {
+                self.p_logs.on_event(event);
             },
+            _ => { self.on_job_key(event); },
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn queue(info: &mut InfoPanel, id: usize) {
+        let job = Job::Delete(PathBuf::from(format!("/tmp/{}", id)));
+        info.on_job(JobEvent::Queued { id, side: Side::Left, job, control: Arc::new(Control::default()) });
+    }
+
+    fn selected_id(info: &InfoPanel) -> Option<usize> {
+        info.job_selected().map(|idx| info.jobs[idx].id)
+    }
+
+    #[test]
+    fn selection_stays_on_its_job() {
+        let mut info = InfoPanel::new();
+        info.tab = TITLES.iter().position(|t| *t == TITLE_QUEUE).unwrap();
+        for id in 1..=3 { queue(&mut info, id); }
+        assert_eq!(selected_id(&info), Some(3));
+
+        info.on_event(Event::Action(Action::Up));
+        assert_eq!(selected_id(&info), Some(2));
+
+        // the rows above leave the queue, a job is queued below
+        info.on_job(JobEvent::Started(1));
+        info.on_job(JobEvent::Finished { id: 1, error: None });
+        queue(&mut info, 4);
+        assert_eq!(selected_id(&info), Some(2));
+        info.on_event(Event::Action(Action::CancelJob));
+        assert!(info.jobs.iter().all(|e| e.control.is_canceled() == (e.id == 2)));
+
+        // gone from the tab, nothing else is picked in its place
+        info.on_job(JobEvent::Finished { id: 2, error: Some("canceled".to_string()) });
+        assert_eq!(selected_id(&info), None);
+        info.on_event(Event::Action(Action::Pause));
+        assert!(info.jobs.iter().all(|e| !e.control.is_paused()));
+    }
+
+    #[test]
+    fn progress_bar_fills_by_eighths() {
+        let bar = |ratio: f64| {
+            let area = Rect::new(0, 0, 4, 1);
+            let mut buf = tui::buffer::Buffer::empty(area);
+            tui::widgets::Widget::render(progress_bar(ratio, &Theme::default()), area, &mut buf);
+            buf.content.iter().map(|cell| cell.symbol.as_str()).collect::<String>()
+        };
+        assert_eq!(bar(0.0), "    ");
+        assert_eq!(bar(0.0625), "▎   ");
+        assert_eq!(bar(0.5625), "██▎ ");
+        assert_eq!(bar(1.0), "████");
+        assert_eq!(bar(f64::NAN), "    ");
+    }
+}
@@ -0,0 +1,495 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::config::KeysConfig;
+
+/// Where keys are looked up, the focused info panel or preview looks in its own before `Normal`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Context {
+    Normal,
+    Info,
+    Preview,
+    Search, // typing a search, other printable keys go to the query
+    Popup,  // printable keys go to inputs
+}
+
+impl Context {
+    pub const ALL: [Context; 5] = [Self::Normal, Self::Info, Self::Preview, Self::Search, Self::Popup];
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Self::Normal  => "Normal",
+            Self::Info    => "Info",
+            Self::Preview => "Preview",
+            Self::Search  => "Search",
+            Self::Popup   => "Popup",
+        }
+    }
+}
+
+macro_rules! actions {
+    ($($variant:ident => $name:literal, $help:literal;)*) => {
+        /// What a key does, named in the config by `name()`.
+        #[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+        pub enum Action {
+            $($variant,)*
+        }
+
+        impl Action {
+            pub const ALL: &'static [Action] = &[$(Self::$variant,)*];
+
+            pub fn name(&self) -> &'static str {
+                match self { $(Self::$variant => $name,)* }
+            }
+
+            pub fn help(&self) -> &'static str {
+                match self { $(Self::$variant => $help,)* }
+            }
+        }
+    };
+}
+
+actions! {
+    // app
+    Quit         => "quit",          "Quit";
+    Help         => "help",          "Show this help";
+    Unfocus      => "unfocus",       "Leave the focused panel";
+    WindowLeft   => "window-left",   "Focus the panel on the left";
+    WindowRight  => "window-right",  "Focus the panel on the right";
+    WindowDown   => "window-down",   "Focus the panel below";
+    WindowUp     => "window-up",     "Focus the panel above";
+    Search       => "search",        "Search in the listing";
+    Execute      => "execute",       "Queue the marked files";
+    NewFile      => "new-file",      "Create a file";
+    NewDir       => "new-dir",       "Create a directory";
+    SelectGlob   => "select-glob",   "Select the names matching a glob";
+    DeselectGlob => "deselect-glob", "Unselect the names matching a glob";
+    Columns      => "columns",       "Edit the columns";
+    Filter       => "filter",        "Edit the filter";
+    Find         => "find",          "Find a file below the directory";
+    Retry        => "retry",         "Retry the failed job";
+    Preview      => "preview",       "Toggle the preview of the file under the cursor";
+
+    // moves
+    Up           => "up",            "Move up";
+    Down         => "down",          "Move down";
+    Left         => "left",          "Move left";
+    Right        => "right",         "Move right";
+    PageUp       => "page-up",       "Scroll a page up";
+    PageDown     => "page-down",     "Scroll a page down";
+    Top          => "top",           "Go to the first row";
+    Bottom       => "bottom",        "Go to the last row";
+
+    // explorer
+    Open         => "open",          "Enter the directory";
+    Parent       => "parent",        "Go to the parent directory";
+    Cwd          => "cwd",           "Go to the working directory";
+    MarkUpload   => "mark-upload",   "Mark for upload";
+    MarkMove     => "mark-move",     "Mark for move";
+    MarkDelete   => "mark-delete",   "Mark for delete";
+    MarkClear    => "mark-clear",    "Clear the mark";
+    Visual       => "visual",        "Toggle the visual range";
+    Select       => "select",        "Toggle the selection of the row";
+    SelectAll    => "select-all",    "Select all, or nothing when all are";
+    SelectInvert => "select-invert", "Invert the selection";
+    Size         => "size",          "Compute the size of directories";
+    AutoSize     => "auto-size",     "Toggle sizing every directory";
+    Sort         => "sort",          "Cycle the sort key";
+    SortReverse  => "sort-reverse",  "Reverse the sort";
+    DirsFirst    => "dirs-first",    "Toggle directories first";
+    Long         => "long",          "Toggle the long listing";
+    SearchNext   => "search-next",   "Go to the next match";
+    SearchPrev   => "search-prev",   "Go to the previous match";
+    SearchClear  => "search-clear",  "Clear the search";
+
+    // info
+    Follow       => "follow",        "Follow the last log";
+    Pause        => "pause",         "Pause or resume the job";
+    CancelJob    => "cancel-job",    "Cancel the job";
+
+    // preview
+    Wrap         => "wrap",          "Toggle wrapping long lines";
+
+    // search and popups
+    Confirm      => "confirm",       "Confirm";
+    Cancel       => "cancel",        "Cancel";
+    Next         => "next",          "Next button";
+    SearchMode   => "search-mode",   "Cycle substring, regex and glob";
+    Backspace    => "backspace",     "Delete the last character";
+    ApplyAll     => "apply-all",     "Apply to all conflicts";
+}
+
+impl std::str::FromStr for Action {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL.iter().copied().find(|action| action.name() == s).ok_or_else(|| format!("unknown action `{}`", s))
+    }
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A key with its modifiers, written like `C-w`, `A-Enter` or `G`.
+///
+/// Shift is part of the character, `G` and `S-g` are the same chord, like `BackTab` and `S-Tab`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct Chord {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+const NAMES: [(KeyCode, &str); 16] = [
+    (KeyCode::Char(' '), "Space"),
+    (KeyCode::Enter,     "Enter"),
+    (KeyCode::Esc,       "Esc"),
+    (KeyCode::Tab,       "Tab"),
+    (KeyCode::BackTab,   "BackTab"),
+    (KeyCode::Backspace, "Backspace"),
+    (KeyCode::Delete,    "Delete"),
+    (KeyCode::Insert,    "Insert"),
+    (KeyCode::Home,      "Home"),
+    (KeyCode::End,       "End"),
+    (KeyCode::PageUp,    "PageUp"),
+    (KeyCode::PageDown,  "PageDown"),
+    (KeyCode::Up,        "Up"),
+    (KeyCode::Down,      "Down"),
+    (KeyCode::Left,      "Left"),
+    (KeyCode::Right,     "Right"),
+];
+
+impl Chord {
+    fn new(mut code: KeyCode, mut modifiers: KeyModifiers) -> Self {
+        if let KeyCode::Char(c) = code {
+            if modifiers.contains(KeyModifiers::SHIFT) { code = KeyCode::Char(c.to_ascii_uppercase()); }
+            modifiers.remove(KeyModifiers::SHIFT);
+        } else if code == KeyCode::BackTab || (code == KeyCode::Tab && modifiers.contains(KeyModifiers::SHIFT)) {
+            code = KeyCode::BackTab;
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        Self { code, modifiers }
+    }
+
+    /// The character it types, if it is a plain one.
+    pub fn text(&self) -> Option<char> {
+        match self.code {
+            KeyCode::Char(c) if self.modifiers.is_empty() => Some(c),
+            _ => None,
+        }
+    }
+}
+
+impl From<KeyEvent> for Chord {
+    fn from(key: KeyEvent) -> Self {
+        Self::new(key.code, key.modifiers)
+    }
+}
+
+impl std::str::FromStr for Chord {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = s;
+        // `C--` is control and minus
+        while rest.len() > 2 && rest.as_bytes()[1] == b'-' {
+            modifiers |= match &rest[..1] {
+                "C" => KeyModifiers::CONTROL,
+                "A" => KeyModifiers::ALT,
+                "S" => KeyModifiers::SHIFT,
+                _   => return Err(format!("unknown modifier in `{}`, use C-, A- or S-", s)),
+            };
+            rest = &rest[2..];
+        }
+
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match NAMES.iter().find(|(_, name)| name.eq_ignore_ascii_case(rest)) {
+                Some((code, _)) => *code,
+                None => match rest.strip_prefix(['F', 'f']).and_then(|n| n.parse().ok()) {
+                    Some(n) if (1..=12).contains(&n) => KeyCode::F(n),
+                    _ => return Err(format!("unknown key `{}`", rest)),
+                },
+            },
+        };
+        Ok(Self::new(code, modifiers))
+    }
+}
+
+impl std::fmt::Display for Chord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) { f.write_str("C-")?; }
+        if self.modifiers.contains(KeyModifiers::ALT) { f.write_str("A-")?; }
+        if self.modifiers.contains(KeyModifiers::SHIFT) { f.write_str("S-")?; }
+        match (self.code, NAMES.iter().find(|(code, _)| *code == self.code)) {
+            (_, Some((_, name))) => f.write_str(name),
+            (KeyCode::Char(c), _) => write!(f, "{}", c),
+            (KeyCode::F(n), _)    => write!(f, "F{}", n),
+            (code, _)             => write!(f, "{:?}", code),
+        }
+    }
+}
+
+/// Chords pressed one after the other, written like `g g` or `C-w h`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Keys(pub Vec<Chord>);
+
+impl std::str::FromStr for Keys {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let chords = s.split_whitespace().map(str::parse).collect::<Result<Vec<Chord>, _>>()?;
+        if chords.is_empty() { return Err("no key".to_owned()); }
+        Ok(Self(chords))
+    }
+}
+
+impl std::fmt::Display for Keys {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (idx, chord) in self.0.iter().enumerate() {
+            if idx > 0 { f.write_str(" ")?; }
+            write!(f, "{}", chord)?;
+        }
+        Ok(())
+    }
+}
+
+// both are written as their names in the config
+impl Serialize for Action {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for Action {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(D::Error::custom)
+    }
+}
+
+impl Serialize for Keys {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Keys {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(D::Error::custom)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+const DEFAULTS: &[(Context, &str, Action)] = &[
+    (Context::Normal, "q",          Action::Quit),
+    (Context::Normal, "?",          Action::Help),
+    (Context::Normal, "Esc",        Action::Unfocus),
+    (Context::Normal, "C-w h",      Action::WindowLeft),
+    (Context::Normal, "C-w l",      Action::WindowRight),
+    (Context::Normal, "C-w j",      Action::WindowDown),
+    (Context::Normal, "C-w k",      Action::WindowUp),
+    (Context::Normal, "/",          Action::Search),
+    (Context::Normal, "x",          Action::Execute),
+    (Context::Normal, "a",          Action::NewFile),
+    (Context::Normal, "A",          Action::NewDir),
+    (Context::Normal, "+",          Action::SelectGlob),
+    (Context::Normal, "-",          Action::DeselectGlob),
+    (Context::Normal, "c",          Action::Columns),
+    (Context::Normal, "|",          Action::Filter),
+    (Context::Normal, "f",          Action::Find),
+    (Context::Normal, "p",          Action::Preview),
+    (Context::Normal, "Up",         Action::Up),
+    (Context::Normal, "k",          Action::Up),
+    (Context::Normal, "Down",       Action::Down),
+    (Context::Normal, "j",          Action::Down),
+    (Context::Normal, "C-u",        Action::PageUp),
+    (Context::Normal, "C-d",        Action::PageDown),
+    (Context::Normal, "g g",        Action::Top),
+    (Context::Normal, "G",          Action::Bottom),
+    (Context::Normal, "Enter",      Action::Open),
+    (Context::Normal, "Backspace",  Action::Parent),
+    (Context::Normal, ".",          Action::Cwd),
+    (Context::Normal, "U",          Action::MarkUpload),
+    (Context::Normal, "M",          Action::MarkMove),
+    (Context::Normal, "D",          Action::MarkDelete),
+    (Context::Normal, "C",          Action::MarkClear),
+    (Context::Normal, "v",          Action::Visual),
+    (Context::Normal, "Space",      Action::Select),
+    (Context::Normal, "C-a",        Action::SelectAll),
+    (Context::Normal, "*",          Action::SelectInvert),
+    (Context::Normal, "s",          Action::Size),
+    (Context::Normal, "S",          Action::AutoSize),
+    (Context::Normal, "o",          Action::Sort),
+    (Context::Normal, "O",          Action::SortReverse),
+    (Context::Normal, "F",          Action::DirsFirst),
+    (Context::Normal, "L",          Action::Long),
+    (Context::Normal, "n",          Action::SearchNext),
+    (Context::Normal, "N",          Action::SearchPrev),
+    (Context::Normal, "C-l",        Action::SearchClear),
+
+    // space is a leader here, it selects in the explorers
+    (Context::Info,   "Space w h",  Action::WindowLeft),
+    (Context::Info,   "Space w l",  Action::WindowRight),
+    (Context::Info,   "Space w j",  Action::WindowDown),
+    (Context::Info,   "Space w k",  Action::WindowUp),
+    (Context::Info,   "Up",         Action::Up),
+    (Context::Info,   "k",          Action::Up),
+    (Context::Info,   "Down",       Action::Down),
+    (Context::Info,   "j",          Action::Down),
+    (Context::Info,   "Left",       Action::Left),
+    (Context::Info,   "h",          Action::Left),
+    (Context::Info,   "Right",      Action::Right),
+    (Context::Info,   "l",          Action::Right),
+    (Context::Info,   "C-l",        Action::Follow),
+    (Context::Info,   "p",          Action::Pause),
+    (Context::Info,   "c",          Action::CancelJob),
+    (Context::Info,   "r",          Action::Retry),
+
+    // moves up and down are the normal ones
+    (Context::Preview, "Left",      Action::Left),
+    (Context::Preview, "h",         Action::Left),
+    (Context::Preview, "Right",     Action::Right),
+    (Context::Preview, "l",         Action::Right),
+    (Context::Preview, "w",         Action::Wrap),
+
+    (Context::Search, "Enter",      Action::Confirm),
+    (Context::Search, "Esc",        Action::Cancel),
+    (Context::Search, "Tab",        Action::SearchMode),
+    (Context::Search, "Backspace",  Action::Backspace),
+
+    (Context::Popup,  "Enter",      Action::Confirm),
+    (Context::Popup,  "Esc",        Action::Cancel),
+    (Context::Popup,  "Left",       Action::Left),
+    (Context::Popup,  "h",          Action::Left),
+    (Context::Popup,  "Right",      Action::Right),
+    (Context::Popup,  "l",          Action::Right),
+    (Context::Popup,  "Up",         Action::Up),
+    (Context::Popup,  "C-p",        Action::Up),
+    (Context::Popup,  "Down",       Action::Down),
+    (Context::Popup,  "C-n",        Action::Down),
+    (Context::Popup,  "Tab",        Action::Next),
+    (Context::Popup,  "a",          Action::ApplyAll),
+];
+
+/// What came out of the keys pressed so far.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Lookup {
+    Action(Action),
+    Pending, // a longer sequence starts with them
+    None,
+}
+
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: Vec<(Context, Keys, Action)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = DEFAULTS.iter()
+            .map(|(context, keys, action)| (*context, keys.parse().expect("default keys are valid"), *action))
+            .collect();
+        Self { bindings }
+    }
+}
+
+impl Keymap {
+    /// The default bindings, with the actions in `config` bound to their keys instead.
+    pub fn new(config: &KeysConfig) -> Self {
+        let mut keymap = Self::default();
+        let tables = [
+            (Context::Normal,  &config.normal),
+            (Context::Info,    &config.info),
+            (Context::Preview, &config.preview),
+            (Context::Search,  &config.search),
+            (Context::Popup,   &config.popup),
+        ];
+        for (context, table) in tables {
+            for (action, keys) in table {
+                keymap.rebind(context, *action, keys);
+            }
+        }
+        keymap
+    }
+
+    /// Bind `action` to `keys` only, they are taken from any other action.
+    fn rebind(&mut self, context: Context, action: Action, keys: &[Keys]) {
+        self.bindings.retain(|(c, k, a)| *c != context || (*a != action && !keys.contains(k)));
+        self.bindings.extend(keys.iter().map(|k| (context, k.clone(), action)));
+    }
+
+    /// Look `keys` up in the first of `contexts` that knows them.
+    ///
+    /// A sequence waits for its next key even if a shorter one is bound.
+    pub fn lookup(&self, contexts: &[Context], keys: &[Chord]) -> Lookup {
+        for context in contexts {
+            let mut found = Lookup::None;
+            for (_, k, action) in self.bindings.iter().filter(|(c, k, _)| c == context && k.0.starts_with(keys)) {
+                if k.0.len() > keys.len() { return Lookup::Pending; }
+                found = Lookup::Action(*action);
+            }
+            if found != Lookup::None { return found; }
+        }
+        Lookup::None
+    }
+
+    /// The actions bound in `context` with their keys, in the order of `Action::ALL`.
+    pub fn bindings(&self, context: Context) -> Vec<(Action, Vec<&Keys>)> {
+        Action::ALL.iter().filter_map(|action| {
+            let keys: Vec<_> = self.bindings.iter()
+                .filter(|(c, _, a)| *c == context && a == action)
+                .map(|(_, k, _)| k)
+                .collect();
+            (!keys.is_empty()).then_some((*action, keys))
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chord(code: KeyCode, modifiers: KeyModifiers) -> Chord {
+        Chord::new(code, modifiers)
+    }
+
+    #[test]
+    fn chord_parsing() {
+        let parse = |s: &str| s.parse::<Chord>();
+        assert_eq!(parse("a"), Ok(chord(KeyCode::Char('a'), KeyModifiers::NONE)));
+        assert_eq!(parse("C-w"), Ok(chord(KeyCode::Char('w'), KeyModifiers::CONTROL)));
+        assert_eq!(parse("C-A-x"), Ok(chord(KeyCode::Char('x'), KeyModifiers::CONTROL | KeyModifiers::ALT)));
+        assert_eq!(parse("A-Enter"), Ok(chord(KeyCode::Enter, KeyModifiers::ALT)));
+        assert_eq!(parse("pagedown"), Ok(chord(KeyCode::PageDown, KeyModifiers::NONE)));
+        assert_eq!(parse("Space"), Ok(chord(KeyCode::Char(' '), KeyModifiers::NONE)));
+        assert_eq!(parse("F12"), Ok(chord(KeyCode::F(12), KeyModifiers::NONE)));
+        assert_eq!(parse("é"), Ok(chord(KeyCode::Char('é'), KeyModifiers::NONE)));
+        // a minus is a key too
+        assert_eq!(parse("-"), Ok(chord(KeyCode::Char('-'), KeyModifiers::NONE)));
+        assert_eq!(parse("C--"), Ok(chord(KeyCode::Char('-'), KeyModifiers::CONTROL)));
+
+        // shift is part of the character
+        assert_eq!(parse("S-g"), parse("G"));
+        assert_eq!(parse("S-Tab"), parse("BackTab"));
+        assert_eq!(Chord::from(KeyEvent::new(KeyCode::Char('G'), KeyModifiers::SHIFT)), parse("G").unwrap());
+
+        for bad in ["", "C-", "X-a", "F0", "F13", "Enterr", "ab"] {
+            assert!(parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn chords_written_back() {
+        for text in ["a", "G", "C-w", "C-A-Enter", "Space", "BackTab", "F5", "C--"] {
+            assert_eq!(text.parse::<Chord>().unwrap().to_string(), text);
+        }
+        assert_eq!("S-tab".parse::<Chord>().unwrap().to_string(), "BackTab");
+        assert_eq!("g  g".parse::<Keys>().unwrap().to_string(), "g g");
+        assert!("  ".parse::<Keys>().is_err());
+        assert!("C-w x-".parse::<Keys>().is_err());
+    }
+}
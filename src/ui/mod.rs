@@ -3,6 +3,8 @@ pub(crate) mod info;
 pub(crate) mod status;
 pub(crate) mod popup;
 pub(crate) mod app;
+pub(crate) mod keymap;
+pub(crate) mod preview;
 
 pub use info::LogRecord;
 pub use app::run;
@@ -10,6 +12,7 @@ pub use panel::Styles as PanelStyles;
 pub use file_explorer::Styles as FeStyles;
 
 use crate::theme::Theme;
+use crate::job::JobEvent;
 
 use std::io;
 use std::sync::mpsc::Receiver;
@@ -32,7 +35,6 @@ use tui::layout::{Alignment, Layout, Constraint, Direction, Rect, Margin};
 
 /// NOTE: no copy here, we will send heay structs in events,
 /// and keep our status Copyable
-#[allow(non_camel_case_types)]
 #[derive(Debug, Clone)]
 pub enum Event {
     // Input behavior
@@ -41,6 +43,7 @@ pub enum Event {
 
     // Activity
     Log(LogRecord),
+    Job(JobEvent),
     Tick,
 
     // Other common commands
@@ -48,20 +51,26 @@ pub enum Event {
     ScrollUp,
     ScrollDown,
     ExitConfirm,
-    Keys_G,
-    Keys_gg,
-    Search(String),
+    /// A key looked up in the keymap.
+    Action(keymap::Action),
+    Search(String, explorer::SearchMode),
+    CreateFile(String),
+    CreateDir(String),
+    SelectGlob(String),
+    DeselectGlob(String),
+    SetColumns(String),
+    SetFilter(String),
+    /// Show the directory of a path with the cursor on it.
+    Reveal(std::path::PathBuf),
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub enum Wait {
-    WaitLeader([char; 5], usize), // Leader key for more keys
-    WaitSearch([char; 20], usize),
+    WaitKeys(Vec<keymap::Chord>), // a sequence bound in the keymap, waiting for more keys
+    WaitSearch(explorer::SearchMode, String), // the query as typed, searched at each key
 
     WaitClick(u16, u16),      // change focus
     WaitClickEdge(u16, u16),  // change size start
-
-    WaitG,
 }
 
 pub trait Ui {
@@ -86,15 +95,30 @@ pub mod panel {
         fn border_active(&self) -> Style;
         fn border_inactive(&self) -> Style;
         fn btn(&self) -> Style;
+        /// fg for the filled part, bg for the track
+        fn progress(&self) -> Style;
+        /// headings in popups
+        fn title(&self) -> Style;
+        fn tabs(&self) -> Style;
+        fn tab_selected(&self) -> Style;
+        fn error(&self) -> Style;
+        fn warning(&self) -> Style;
+        fn dim(&self) -> Style;
+        fn log(&self, level: log::Level) -> Style;
     }
 }
 
 pub mod file_explorer {
+    use crate::ls_colors::LsColors;
     use tui::style::Style;
 
     pub trait Styles {
         fn mark(&self) -> Style;
         fn cursor_select(&self) -> Style;
+        /// rows in the selection or in the visual range
+        fn selected(&self) -> Style;
+        /// toggles that are on, in the footer
+        fn flag(&self) -> Style;
 
         fn highlight(&self) -> Style;
 
@@ -109,10 +133,15 @@ pub mod file_explorer {
         fn dot_dot(&self) -> Style;
         fn file(&self) -> Style;
         fn sym_link(&self) -> Style;
+        fn broken_link(&self) -> Style;
         fn other(&self) -> Style;
+        /// names by kind and extension, before the styles of their type
+        fn ls_colors(&self) -> &LsColors;
 
         // Permissions
         fn dashed(&self) -> Style;
+        /// setuid, setgid and sticky
+        fn special_bit(&self) -> Style;
         fn user_read(&self) -> Style;
         fn user_write(&self) -> Style;
         fn user_execute(&self) -> Style;
@@ -1,9 +1,24 @@
 use super::*;
+use super::explorer::{format_size, name_style, render_name};
+use super::keymap::{Action, Context, Keymap};
+
+use crate::fs::{FileType, Host};
+use crate::ls_colors::Indicator;
+use crate::job::{Conflict, Endpoint, Resolution};
+use crate::util::{crop_line, fuzzy_match};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
 pub enum PopupPanel {
     Exit(YesOrNo),
+    Message(YesOrNo),
+    Conflict(ConflictPanel),
     Help(HelpPanel),
     Input(InputPanel),
+    Finder(FinderPanel),
 }
 
 // NOTE: Fix not mean accurate size, you still render in smaller size if terminal is too small
@@ -18,12 +33,36 @@ impl PopupPanel {
         Self::Exit(YesOrNo::new(
             None,
             "exit filez?".to_owned(),
-            [Some(Event::ExitConfirm), None, None],
+            vec!["Ok", "No"],
+            vec![Some(Event::ExitConfirm), None, None],
         ))
     }
 
-    pub fn new_help() -> Self {
-        Self::Help(HelpPanel::new())
+    pub fn new_error(msg: String) -> Self {
+        Self::Message(YesOrNo::new(
+            Some("Error".to_owned()),
+            msg,
+            vec!["Ok"],
+            vec![None, None],
+        ))
+    }
+
+    pub fn new_conflict(conflict: Conflict) -> Self {
+        Self::Conflict(ConflictPanel::new(conflict))
+    }
+
+    pub fn new_help(keymap: &Keymap) -> Self {
+        Self::Help(HelpPanel::new(keymap))
+    }
+
+    /// Find files below `root`, walked with `host` in the background.
+    pub fn new_finder(host: Box<dyn Host + Send>, root: PathBuf) -> Self {
+        Self::Finder(FinderPanel::new(host, root))
+    }
+
+    /// Ask for a line, `on_submit` turns it into the event emitted on Enter.
+    pub fn new_input(title: String, initial: String, history: Vec<String>, on_submit: fn(String) -> Event) -> Self {
+        Self::Input(InputPanel::new(title, initial, history, on_submit))
     }
 }
 
@@ -33,9 +72,9 @@ pub struct YesOrNo {
 
     exit: bool,
     state: usize,
-    rtn: [Option<Event>; 3],
-    r_ok: Rect,
-    r_no: Rect,
+    buttons: Vec<&'static str>,
+    rtn: Vec<Option<Event>>, // one for each button, the last one when dismissed
+    r_btns: Vec<Rect>,
     r_wd: Rect,
 }
 
@@ -43,24 +82,252 @@ impl YesOrNo {
     fn new(
         title: Option<String>,
         msg: String,
-        rtn: [Option<Event>; 3],
+        buttons: Vec<&'static str>,
+        rtn: Vec<Option<Event>>,
     ) -> Self {
-        Self { title, msg, exit: false, state: 0, rtn, r_ok: Rect::default(), r_no: Rect::default(), r_wd: Default::default() }
+        assert_eq!(buttons.len() + 1, rtn.len());
+        Self { title, msg, exit: false, state: 0, buttons, rtn, r_btns: vec![], r_wd: Default::default() }
+    }
+}
+
+pub struct ConflictPanel {
+    conflict: Conflict,
+    choice: YesOrNo,
+    all: bool,
+    r_all: Rect,
+}
+
+const RESOLUTIONS: [(Resolution, &str); 5] = [
+    (Resolution::Overwrite,        "Overwrite"),
+    (Resolution::Skip,             "Skip"),
+    (Resolution::Rename,           "Rename"),
+    (Resolution::OverwriteIfNewer, "If newer"),
+    (Resolution::Compare,          "Compare"),
+];
+
+impl ConflictPanel {
+    fn new(conflict: Conflict) -> Self {
+        let msg = format!("`{}` already exists", conflict.dst.path.display());
+        let buttons = RESOLUTIONS.iter().map(|(_, name)| *name).collect();
+        let choice = YesOrNo::new(Some("Conflict".to_owned()), msg, buttons, vec![None; RESOLUTIONS.len() + 1]);
+        Self { conflict, choice, all: false, r_all: Rect::default() }
     }
 }
 
 pub struct InputPanel {
+    title: String,
+    line: String,
+    cursor: usize,  // byte offset, always at a grapheme boundary
+    offset: usize,  // columns scrolled out on the left
+
+    history: Vec<String>,
+    history_idx: Option<usize>,
+    draft: String,  // the line being edited before browsing the history
+    yank: String,   // last text killed by Ctrl-W/Ctrl-U/Ctrl-K
+
+    on_submit: fn(String) -> Event,
+    submitted: bool,
+    exit: bool,
+    rect: Rect,
+}
+
+impl InputPanel {
+    fn new(title: String, initial: String, history: Vec<String>, on_submit: fn(String) -> Event) -> Self {
+        Self {
+            title,
+            cursor: initial.len(),
+            line: initial,
+            offset: 0,
+
+            history,
+            history_idx: None,
+            draft: String::new(),
+            yank: String::new(),
+
+            on_submit,
+            submitted: false,
+            exit: false,
+            rect: Rect::default(),
+        }
+    }
+
+    /// The line entered, if the input is confirmed.
+    pub fn submitted(&self) -> Option<&str> {
+        if self.submitted { Some(&self.line) } else { None }
+    }
+
+    fn prev_boundary(&self) -> usize {
+        self.line[..self.cursor].grapheme_indices(true).next_back().map(|(idx, _)| idx).unwrap_or(0)
+    }
+
+    fn next_boundary(&self) -> usize {
+        self.line[self.cursor..].graphemes(true).next().map(|g| self.cursor + g.len()).unwrap_or(self.cursor)
+    }
+
+    fn insert(&mut self, text: &str) {
+        self.line.insert_str(self.cursor, text);
+        self.cursor += text.len();
+    }
+
+    fn kill(&mut self, start: usize, end: usize) {
+        if start < end {
+            self.yank = self.line.drain(start..end).collect();
+            self.cursor = start;
+        }
+    }
+
+    /// Back to the start of the word before the cursor, words are split by whitespace.
+    fn word_start(&self) -> usize {
+        let before = self.line[..self.cursor].trim_end();
+        before.rfind(char::is_whitespace).map(|idx| idx + 1).unwrap_or(0)
+    }
+
+    fn browse(&mut self, older: bool) {
+        let idx = match (self.history_idx, older) {
+            (None, true) if !self.history.is_empty() => {
+                self.draft = self.line.clone();
+                Some(self.history.len() - 1)
+            },
+            (Some(idx), true)  => Some(idx.saturating_sub(1)),
+            (Some(idx), false) if idx + 1 < self.history.len() => Some(idx + 1),
+            (Some(_), false)   => None,
+            (None, _)          => return,
+        };
+        self.history_idx = idx;
+        self.line = match idx {
+            Some(idx) => self.history[idx].clone(),
+            None => std::mem::take(&mut self.draft),
+        };
+        self.cursor = self.line.len();
+    }
+}
+
+/// Fuzzy search of the paths below a directory, the tree is indexed while typing.
+pub struct FinderPanel {
+    root: PathBuf,
+    query: InputPanel,
+
+    index: Vec<(String, FileType)>, // paths relative to `root`
+    rx: Receiver<Vec<(String, FileType)>>,
+    indexing: bool,
+
+    results: Vec<Hit>,
+    scored: usize, // entries of the index already matched against `pattern`
+    pattern: String,
+    select: usize,
+    offset: usize,
+
+    chosen: Option<PathBuf>,
+    exit: bool,
+    rect: Rect,
+    r_list: Rect,
+}
+
+impl FinderPanel {
+    /// Stop indexing past this many entries, the popup shouldn't eat the memory of a whole disk.
+    const LIMIT: usize = 500_000;
+
+    fn new(mut host: Box<dyn Host + Send>, root: PathBuf) -> Self {
+        let (tx, rx) = channel();
+        let walk = root.clone();
+        // breadth first, the closer entries come first, the walk stops once the popup is gone
+        std::thread::spawn(move || {
+            let mut dirs = VecDeque::from([PathBuf::new()]);
+            let mut count = 0;
+            while let Some(rel) = dirs.pop_front() {
+                let files = match host.read_dir(&walk.join(&rel)) {
+                    Ok(files) => files,
+                    Err(e) => { log::debug!("Fail to index `{}`: {}", walk.join(&rel).display(), e); continue; },
+                };
+                let mut batch = vec![];
+                for file in files.into_iter().filter(|f| !f.is_dot_dot()) {
+                    let rel = rel.join(&file.name);
+                    // symlinks aren't followed, they could loop
+                    if file.file_type == FileType::Dir { dirs.push_back(rel.clone()); }
+                    batch.push((rel.to_string_lossy().into_owned(), file.file_type));
+                }
+                count += batch.len();
+                if tx.send(batch).is_err() || count >= Self::LIMIT { return; }
+            }
+        });
+
+        Self {
+            root,
+            query: InputPanel::new("Find".to_owned(), String::new(), vec![], |_| Event::Tick),
 
+            index: vec![],
+            rx,
+            indexing: true,
+
+            results: vec![],
+            scored: 0,
+            pattern: String::new(),
+            select: 0,
+            offset: 0,
+
+            chosen: None,
+            exit: false,
+            rect: Rect::default(),
+            r_list: Rect::default(),
+        }
+    }
+
+    /// Take the entries indexed since the last time and match what isn't yet.
+    fn update(&mut self) {
+        loop {
+            match self.rx.try_recv() {
+                Ok(batch) => { self.index.extend(batch); },
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => { self.indexing = false; break; },
+            }
+        }
+
+        if self.query.line != self.pattern {
+            self.pattern = self.query.line.clone();
+            self.results.clear();
+            self.scored = 0;
+            self.select = 0;
+        }
+        if self.scored == self.index.len() { return; }
+
+        for (idx, (path, _)) in self.index.iter().enumerate().skip(self.scored) {
+            if let Some((score, ranges)) = fuzzy_match(&self.pattern, path) {
+                self.results.push(Hit { score, entry: idx, ranges });
+            }
+        }
+        self.scored = self.index.len();
+        let index = &self.index;
+        self.results.sort_by(|h1, h2| h2.score.cmp(&h1.score).then_with(|| index[h1.entry].0.len().cmp(&index[h2.entry].0.len())));
+    }
+}
+
+struct Hit {
+    score: i64,
+    entry: usize, // in the index
+    ranges: Vec<(usize, usize)>,
 }
 
 pub struct HelpPanel {
+    rows: Vec<(String, &'static str)>, // keys and what they do, a title has no keys
+    offset: usize,
     exit: bool,
     rect: Rect,
 }
 
 impl HelpPanel {
-    fn new() -> Self {
+    fn new(keymap: &Keymap) -> Self {
+        let mut rows = vec![];
+        for context in Context::ALL {
+            if !rows.is_empty() { rows.push((String::new(), "")); }
+            rows.push((String::new(), context.title()));
+            for (action, keys) in keymap.bindings(context) {
+                let keys: Vec<_> = keys.iter().map(|k| k.to_string()).collect();
+                rows.push((keys.join(", "), action.help()));
+            }
+        }
         Self {
+            rows,
+            offset: 0,
             exit: false,
             rect: Default::default(),
         }
@@ -72,8 +339,11 @@ macro_rules! auto_popup {
     ($slf:ident, $inner:ident, $code:stmt ) => {
         match $slf {
             PopupPanel::Exit($inner)  => { $code },
+            PopupPanel::Message($inner) => { $code },
+            PopupPanel::Conflict($inner) => { $code },
             PopupPanel::Help($inner)  => { $code },
             PopupPanel::Input($inner) => { $code },
+            PopupPanel::Finder($inner) => { $code },
         }
     };
 }
@@ -89,6 +359,13 @@ impl Ui for PopupPanel {
 
 }
 
+impl PopupPanel {
+    /// Printable keys are typed in it instead of looked up in the keymap.
+    pub fn takes_text(&self) -> bool {
+        matches!(self, Self::Input(_) | Self::Finder(_))
+    }
+}
+
 impl Popup for PopupPanel {
     fn suit_in(&self, rect: Rect) -> Rect {
         auto_popup!(self, inner, inner.suit_in(rect))
@@ -146,50 +423,35 @@ impl Ui for YesOrNo {
         });
         f.render_widget(Paragraph::new(&*self.msg).alignment(Alignment::Center).wrap(Wrap { trim: true }), r_msg);
 
-        let chunks = Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints([
-                Constraint::Percentage(50),
-                Constraint::Percentage(50),
-            ])
-            .split(r_btn);
-        self.r_ok = chunks[0]; self.r_no = chunks[1];
-
-        let mut b_ok = Paragraph::new("Ok").alignment(Alignment::Center); if self.state == 0 { b_ok = b_ok.style(theme.btn()); }
-        let mut b_no = Paragraph::new("No").alignment(Alignment::Center); if self.state == 1 { b_no = b_no.style(theme.btn()); }
-        f.render_widget(b_ok, self.r_ok);
-        f.render_widget(b_no, self.r_no);
+        self.draw_buttons(f, r_btn, theme);
     }
 
     fn on_event(&mut self, event: Event) {
         match event {
-            Event::Key(KeyEvent { code: KeyCode::Esc, .. }) => {
-                self.state = 2;
+            Event::Action(Action::Cancel) => {
+                self.state = self.buttons.len();
                 self.exit = true;
             },
-            Event::Key(KeyEvent { code: KeyCode::Enter, .. }) => {
+            Event::Action(Action::Confirm) => {
                 self.exit = true;
             },
-            Event::Key(KeyEvent { code: KeyCode::Right | KeyCode::Char('l'), .. }) => {
+            Event::Action(Action::Right) => {
                 self.state += 1;
-                self.state = self.state.min(1);
+                self.state = self.state.min(self.buttons.len() - 1);
             },
-            Event::Key(KeyEvent { code: KeyCode::Left | KeyCode::Char('h'), .. }) => {
+            Event::Action(Action::Left) => {
                 self.state = self.state.saturating_sub(1);
             },
-            Event::Key(KeyEvent { code: KeyCode::Tab, .. }) => {
+            Event::Action(Action::Next) => {
                 self.state += 1;
-                if self.state > 1 { self.state = 0; }
+                if self.state >= self.buttons.len() { self.state = 0; }
             },
             Event::Click(column, row) => {
-                if self.r_ok.contain(column, row) {
-                    self.state = 0;
-                    self.exit = true;
-                } else if self.r_no.contain(column, row) {
-                    self.state = 1;
+                if let Some(idx) = self.r_btns.iter().position(|r| r.contain(column, row)) {
+                    self.state = idx;
                     self.exit = true;
                 } else if !self.r_wd.contain(column, row) {
-                    self.state = 2;
+                    self.state = self.buttons.len();
                     self.exit = true;
                 }
             },
@@ -198,20 +460,288 @@ impl Ui for YesOrNo {
     }
 }
 
+impl YesOrNo {
+    /// Buttons side by side in one line, the hit rects are kept for clicks.
+    fn draw_buttons<B: Backend>(&mut self, f: &mut Frame<B>, rect: Rect, theme: &Theme) {
+        let count = self.buttons.len() as u32;
+        self.r_btns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(vec![Constraint::Ratio(1, count); self.buttons.len()])
+            .split(rect);
+
+        for (idx, (name, r_btn)) in self.buttons.iter().zip(self.r_btns.iter()).enumerate() {
+            let mut b = Paragraph::new(*name).alignment(Alignment::Center);
+            if self.state == idx { b = b.style(theme.btn()); }
+            f.render_widget(b, *r_btn);
+        }
+    }
+}
+
+impl Popup for ConflictPanel {
+    fn suit_in(&self, rect: Rect) -> Rect {
+        rect.inner(&Margin {
+            vertical: rect.height.saturating_sub(12) / 2,
+            horizontal: rect.width.saturating_sub(70) / 2,
+        })
+    }
+
+    /// Answer the worker directly, dismissing means skip.
+    fn emit(self) -> Option<Event> {
+        let resolution = RESOLUTIONS.get(self.choice.state).map(|(r, _)| *r).unwrap_or(Resolution::Skip);
+        if self.conflict.reply.send((resolution, self.all)).is_err() {
+            log::error!("Job #{} doesn't wait for an answer any more", self.conflict.id);
+        }
+        None
+    }
+
+    fn exit(&self) -> bool { self.choice.exit }
+}
+
+impl Ui for ConflictPanel {
+    fn draw<B: Backend>(&mut self, f: &mut Frame<B>, rect: Rect, theme: &Theme) {
+        self.choice.r_wd = rect;
+        let mut block = Block::default().borders(Borders::ALL).border_style(theme.border_active());
+        if let Some(title) = &self.choice.title { block = block.title(&**title); }
+        let r_inner = block.inner(rect);
+        f.render_widget(block, rect);
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Min(1),
+                Constraint::Length(1),
+                Constraint::Length(1),
+            ])
+            .split(r_inner);
+        if chunks.iter().any(|r| r.area() == 0) { return; }
+
+        let endpoint = |title: &'static str, e: &Endpoint| Spans::from(vec![
+            Span::styled(title, theme.title()),
+            Span::raw(if e.is_dir { "directory".to_string() } else { format_size(e.len) }),
+            Span::raw(", modified "),
+            Span::raw(e.modified.map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string()).unwrap_or_else(|| "-".to_string())),
+        ]);
+        let mut text = vec![
+            Spans::from(&*self.choice.msg),
+            Spans::default(),
+            endpoint("source: ", &self.conflict.src),
+            endpoint("target: ", &self.conflict.dst),
+        ];
+        if let Some(note) = &self.conflict.note {
+            text.push(Spans::default());
+            text.push(Spans::from(Span::styled(&**note, theme.warning())));
+        }
+        f.render_widget(Paragraph::new(text).wrap(Wrap { trim: false }), chunks[0]);
+
+        let check = if self.all { "[x]" } else { "[ ]" };
+        self.r_all = Rect { width: 20.min(chunks[1].width), .. chunks[1] };
+        f.render_widget(Paragraph::new(format!("{} (a)pply to all", check)), self.r_all);
+
+        self.choice.draw_buttons(f, chunks[2], theme);
+    }
+
+    fn on_event(&mut self, event: Event) {
+        match event {
+            Event::Action(Action::ApplyAll) => {
+                self.all = !self.all;
+            },
+            Event::Click(column, row) if self.r_all.contain(column, row) => {
+                self.all = !self.all;
+            },
+            _ => { self.choice.on_event(event); }
+        }
+    }
+}
+
 impl Popup for InputPanel {
-    fn suit_in(&self, rect: Rect) -> Rect { rect }
+    fn suit_in(&self, rect: Rect) -> Rect {
+        rect.inner(&Margin {
+            vertical: rect.height.saturating_sub(3) / 2,
+            horizontal: rect.width.saturating_sub(60) / 2,
+        })
+    }
 
-    fn emit(self) -> Option<Event> { todo!() }
-    fn exit(&self) -> bool { todo!() }
+    fn emit(self) -> Option<Event> {
+        if self.submitted { Some((self.on_submit)(self.line)) } else { None }
+    }
+
+    fn exit(&self) -> bool { self.exit }
 }
 
 impl Ui for InputPanel {
-    fn draw<B: Backend>(&mut self, f: &mut Frame<B>, rect: Rect, theme: &Theme) { todo!() }
+    fn draw<B: Backend>(&mut self, f: &mut Frame<B>, rect: Rect, theme: &Theme) {
+        self.rect = rect;
+        let block = Block::default().title(&*self.title).borders(Borders::ALL).border_style(theme.border_active());
+        let r_inner = block.inner(rect);
+        f.render_widget(block, rect);
+        if r_inner.area() == 0 { return; }
+
+        // keep the cursor in sight, with a cell for it at the end
+        let width = r_inner.width as usize;
+        let column = self.line[..self.cursor].width();
+        if column < self.offset {
+            self.offset = column;
+        } else if column >= self.offset + width {
+            self.offset = column + 1 - width;
+        }
+
+        f.render_widget(Paragraph::new(crop_line(Spans::from(&*self.line), self.offset)), r_inner);
+        f.set_cursor(r_inner.x + (column - self.offset) as u16, r_inner.y);
+    }
+
+    fn on_event(&mut self, event: Event) {
+        let (code, modifiers) = match event {
+            Event::Key(KeyEvent { code, modifiers }) => (code, modifiers),
+            Event::Action(Action::Cancel) => { self.exit = true; return; },
+            Event::Action(Action::Confirm) => {
+                self.submitted = true;
+                self.exit = true;
+                return;
+            },
+            Event::Action(Action::Left)  => { self.cursor = self.prev_boundary(); return; },
+            Event::Action(Action::Right) => { self.cursor = self.next_boundary(); return; },
+            Event::Action(Action::Up)    => { self.browse(true); return; },
+            Event::Action(Action::Down)  => { self.browse(false); return; },
+            Event::Click(column, row) => {
+                if !self.rect.contain(column, row) { self.exit = true; }
+                return;
+            },
+            _ => return,
+        };
+
+        // the editing keys of readline
+        match (code, modifiers) {
+            (KeyCode::Char('b'), KeyModifiers::CONTROL) => {
+                self.cursor = self.prev_boundary();
+            },
+            (KeyCode::Char('f'), KeyModifiers::CONTROL) => {
+                self.cursor = self.next_boundary();
+            },
+            (KeyCode::Home, _) | (KeyCode::Char('a'), KeyModifiers::CONTROL) => {
+                self.cursor = 0;
+            },
+            (KeyCode::End, _) | (KeyCode::Char('e'), KeyModifiers::CONTROL) => {
+                self.cursor = self.line.len();
+            },
+            (KeyCode::Backspace, _) | (KeyCode::Char('h'), KeyModifiers::CONTROL) => {
+                let start = self.prev_boundary();
+                self.line.replace_range(start..self.cursor, "");
+                self.cursor = start;
+            },
+            (KeyCode::Delete, _) | (KeyCode::Char('d'), KeyModifiers::CONTROL) => {
+                let end = self.next_boundary();
+                self.line.replace_range(self.cursor..end, "");
+            },
+            (KeyCode::Char('w'), KeyModifiers::CONTROL) => {
+                self.kill(self.word_start(), self.cursor);
+            },
+            (KeyCode::Char('u'), KeyModifiers::CONTROL) => {
+                self.kill(0, self.cursor);
+            },
+            (KeyCode::Char('k'), KeyModifiers::CONTROL) => {
+                self.kill(self.cursor, self.line.len());
+            },
+            (KeyCode::Char('y'), KeyModifiers::CONTROL) => {
+                let yank = self.yank.clone();
+                self.insert(&yank);
+            },
+
+            // a paste arrives as plain key presses
+            (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                let mut buf = [0; 4];
+                self.insert(c.encode_utf8(&mut buf));
+            },
+            _ => { },
+        }
+    }
+}
+
+impl Popup for FinderPanel {
+    fn suit_in(&self, rect: Rect) -> Rect {
+        let width = (rect.width * 2 / 3).max(60);
+        let height = rect.height * 2 / 3;
+        rect.inner(&Margin {
+            vertical: rect.height.saturating_sub(height) / 2,
+            horizontal: rect.width.saturating_sub(width) / 2,
+        })
+    }
+
+    fn emit(self) -> Option<Event> {
+        self.chosen.map(Event::Reveal)
+    }
+
+    fn exit(&self) -> bool { self.exit }
+}
+
+impl Ui for FinderPanel {
+    fn draw<B: Backend>(&mut self, f: &mut Frame<B>, rect: Rect, theme: &Theme) {
+        self.update();
+        self.rect = rect;
+        self.query.title = format!("Find in {} ({}/{}{})",
+            self.root.display(), self.results.len(), self.index.len(), if self.indexing { "..." } else { "" });
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(3), Constraint::Min(0)])
+            .split(rect);
+        self.query.draw(f, chunks[0], theme);
+
+        let block = Block::default().borders(Borders::ALL).border_style(theme.border_active());
+        self.r_list = block.inner(chunks[1]);
+        f.render_widget(block, chunks[1]);
+
+        // keep the selection in sight
+        let height = self.r_list.height as usize;
+        if self.select < self.offset {
+            self.offset = self.select;
+        } else if height > 0 && self.select >= self.offset + height {
+            self.offset = self.select + 1 - height;
+        }
+
+        let items: Vec<_> = self.results.iter().enumerate().skip(self.offset).take(height)
+            .map(|(idx, hit)| {
+                let (path, file_type) = &self.index[hit.entry];
+                let item = ListItem::new(Spans::from(render_name(path, &hit.ranges, theme, name_style(*file_type, Indicator::of_type(*file_type), path, theme))));
+                if idx == self.select { item.style(theme.selected()) } else { item }
+            })
+            .collect();
+        f.render_widget(List::new(items), self.r_list);
+    }
+
+    fn on_event(&mut self, event: Event) {
+        match event {
+            Event::Action(Action::Cancel) => {
+                self.exit = true;
+            },
+            Event::Action(Action::Confirm) => {
+                if let Some(hit) = self.results.get(self.select) {
+                    self.chosen = Some(self.root.join(&self.index[hit.entry].0));
+                }
+                self.exit = true;
+            },
+            Event::Action(Action::Up) => {
+                self.select = self.select.saturating_sub(1);
+            },
+            Event::Action(Action::Down) => {
+                self.select = (self.select + 1).min(self.results.len().saturating_sub(1));
+            },
+            Event::Click(column, row) => {
+                if !self.rect.contain(column, row) {
+                    self.exit = true;
+                } else if self.r_list.contain(column, row) {
+                    let idx = self.offset + (row - self.r_list.y) as usize;
+                    if idx < self.results.len() { self.select = idx; }
+                }
+            },
+            Event::Key(..) | Event::Action(..) => { self.query.on_event(event); },
+            _ => { },
+        }
+    }
 }
 
 impl Popup for HelpPanel {
     fn suit_in(&self, rect: Rect) -> Rect {
-        let width = rect.width * 1 / 3;
+        let width = (rect.width / 3).max(60);
         let height = rect.height * 5 / 6;
         rect.inner(&Margin {
             vertical: (rect.height.saturating_sub(height) / 2).max(3),
@@ -235,33 +765,36 @@ impl Ui for HelpPanel {
         let r_inner = block.inner(rect);
         f.render_widget(block, rect);
 
+        self.offset = self.offset.min(self.rows.len().saturating_sub(r_inner.height as usize));
+        let key_width = self.rows.iter().map(|(keys, _)| keys.width()).max().unwrap_or(0) as u16;
+        let rows: Vec<_> = self.rows[self.offset..].iter()
+            .map(|(keys, help)| {
+                if keys.is_empty() {
+                    Row::new(vec!["", help]).style(theme.title())
+                } else {
+                    Row::new(vec![keys.as_str(), help])
+                }
+            })
+            .collect();
         f.render_widget(
-            Table::new(vec![
-                // Row can be created from simple strings.
-                Row::new(vec!["Row11", "Row12", "Row13"]),
-                // You can style the entire row.
-                Row::new(vec!["Row21", "Row22", "Row23"]).style(Style::default().fg(Color::Blue))]
-            ).widths(&[Constraint::Length(5), Constraint::Length(5), Constraint::Length(10)]),
+            Table::new(rows).column_spacing(2).widths(&[Constraint::Length(key_width), Constraint::Min(0)]),
             r_inner
         );
     }
 
     fn on_event(&mut self, event: Event) {
         match event {
-            Event::Key(KeyEvent { code: KeyCode::Esc, .. }) => {
+            Event::Action(Action::Cancel | Action::Confirm) => {
                 self.exit = true;
             },
-            // Event::Key(KeyEvent { code: KeyCode::Right | KeyCode::Char('l'), .. }) => {
-            //     self.state += 1;
-            //     self.state = self.state.min(1);
-            // },
-            // Event::Key(KeyEvent { code: KeyCode::Left | KeyCode::Char('h'), .. }) => {
-            //     self.state = self.state.saturating_sub(1);
-            // },
-            Event::Click(column, row) => {
-                if !self.rect.contain(column, row) {
-                    self.exit = true;
-                }
+            Event::Action(Action::Up) | Event::ScrollUp => {
+                self.offset = self.offset.saturating_sub(1);
+            },
+            Event::Action(Action::Down) | Event::ScrollDown => {
+                self.offset += 1; // bounded when drawn
+            },
+            Event::Click(column, row) if !self.rect.contain(column, row) => {
+                self.exit = true;
             },
             _ => {  }
         }
@@ -0,0 +1,378 @@
+use super::*;
+use super::explorer::{format_size, name_style, render_name};
+use super::keymap::Action;
+
+use crate::fs::{File, FileType, Host};
+use crate::ls_colors::Indicator;
+use crate::util::{natural_cmp, wrap_text};
+use crate::widgets::List2;
+
+use std::fmt::Write;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use unicode_width::UnicodeWidthChar;
+
+/// Bytes read of a file, a huge one is only shown up to here.
+const MAX_BYTES: u64 = 256 * 1024;
+/// Entries shown of a directory.
+const MAX_ENTRIES: usize = 1000;
+const TAB_WIDTH: usize = 4;
+const BYTES_PER_LINE: usize = 16;
+/// Columns a horizontal move scrolls.
+const STEP_X: usize = 4;
+
+enum Content {
+    Loading,
+    Text(Vec<String>),
+    Hex(Vec<u8>),
+    Dir(Vec<File>),
+    Empty(&'static str), // nothing to show, and why
+    Error(String),
+}
+
+/// Shows the file under the cursor of an explorer: text, a hex dump of binaries or the listing of a directory.
+pub struct PreviewPanel {
+    loader: Option<Loader>,
+    path: Option<PathBuf>, // shown, or being read
+    content: Content,
+    cut: bool, // the content stops at the limit
+
+    offset_y: usize, // in lines of the content
+    offset_x: usize,
+    wrap: bool,
+    rect: Rect,
+}
+
+/// Reads files in the background, only the last path asked is read.
+struct Loader {
+    tx: Sender<PathBuf>,
+    rx: Receiver<(PathBuf, Content, bool)>,
+}
+
+impl Loader {
+    fn spawn(mut host: Box<dyn Host + Send>) -> Self {
+        let (tx, rx_paths) = channel::<PathBuf>();
+        let (tx_contents, rx) = channel();
+
+        std::thread::spawn(move || {
+            while let Ok(mut path) = rx_paths.recv() {
+                // the cursor moved on meanwhile
+                if let Some(last) = rx_paths.try_iter().last() { path = last; }
+                let (content, cut) = load(host.as_mut(), &path).unwrap_or_else(|e| (Content::Error(e.to_string()), false));
+                if tx_contents.send((path, content, cut)).is_err() { break; }
+            }
+        });
+
+        Self { tx, rx }
+    }
+}
+
+/// Links show what they point to.
+fn load(host: &mut dyn Host, path: &Path) -> io::Result<(Content, bool)> {
+    let mut file = host.stat(path)?;
+    if file.is_symlink() {
+        let target = host.canonicalize(path)?;
+        file = host.stat(&target)?;
+    }
+
+    match file.file_type {
+        FileType::Dir | FileType::DotDot => {
+            let mut files: Vec<_> = host.read_dir(&file.path)?.into_iter().filter(|f| !f.is_dot_dot()).collect();
+            files.sort_by(|f1, f2| f2.is_dir().cmp(&f1.is_dir()).then_with(|| natural_cmp(&f1.name, &f2.name)));
+            let cut = files.len() > MAX_ENTRIES;
+            files.truncate(MAX_ENTRIES);
+            Ok((Content::Dir(files), cut))
+        },
+        FileType::File => {
+            let mut bytes = vec![];
+            host.open_read(&file.path)?.take(MAX_BYTES + 1).read_to_end(&mut bytes)?;
+            let cut = bytes.len() as u64 > MAX_BYTES;
+            bytes.truncate(MAX_BYTES as usize);
+            Ok((to_content(bytes, cut), cut))
+        },
+        // reading a fifo would wait for a writer
+        _ => Ok((Content::Empty("Nothing to read in a special file"), false)),
+    }
+}
+
+/// Text when it is UTF-8 without NUL, a character cut by the limit is dropped.
+fn to_content(bytes: Vec<u8>, cut: bool) -> Content {
+    if bytes.contains(&0) { return Content::Hex(bytes); }
+    let valid = match std::str::from_utf8(&bytes) {
+        Ok(text) => text.len(),
+        Err(e) if cut && e.error_len().is_none() => e.valid_up_to(),
+        Err(_) => { return Content::Hex(bytes); },
+    };
+    let text = std::str::from_utf8(&bytes[..valid]).unwrap_or_default();
+    Content::Text(text.lines().map(printable).collect())
+}
+
+/// Tabs and control characters would mess the terminal up.
+fn printable(line: &str) -> String {
+    let mut text = String::with_capacity(line.len());
+    let mut column = 0;
+    for c in line.chars() {
+        match c {
+            '\t' => {
+                let spaces = TAB_WIDTH - column % TAB_WIDTH;
+                text.push_str(&" ".repeat(spaces));
+                column += spaces;
+            },
+            '\r' => { },
+            c if c.is_control() => { text.push('\u{fffd}'); column += 1; },
+            c => { text.push(c); column += c.width().unwrap_or(0); },
+        }
+    }
+    text
+}
+
+/// `00000010  2e 72 73 00 ...  |.rs.|` like `hexdump -C`.
+fn hex_line<S: PanelStyles>(offset: usize, chunk: &[u8], style: &S) -> Spans<'static> {
+    let mut hex = String::new();
+    for idx in 0..BYTES_PER_LINE {
+        match chunk.get(idx) {
+            Some(byte) => { let _ = write!(hex, "{:02x} ", byte); },
+            None       => { hex.push_str("   "); },
+        }
+        if idx == BYTES_PER_LINE / 2 - 1 { hex.push(' '); }
+    }
+    let ascii: String = chunk.iter().map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }).collect();
+    Spans::from(vec![
+        Span::styled(format!("{:08x}  ", offset), style.dim()),
+        Span::raw(hex),
+        Span::styled(format!(" |{}|", ascii), style.dim()),
+    ])
+}
+
+impl PreviewPanel {
+    pub fn new() -> Self {
+        Self {
+            loader: None,
+            path: None,
+            content: Content::Empty(""),
+            cut: false,
+
+            offset_y: 0,
+            offset_x: 0,
+            wrap: false,
+            rect: Rect::default(),
+        }
+    }
+
+    /// Preview the files of `host` from now on.
+    pub fn open(&mut self, host: Box<dyn Host + Send>) {
+        self.loader = Some(Loader::spawn(host));
+        self.show(None);
+    }
+
+    /// Stop reading, the thread ends with the loader.
+    pub fn close(&mut self) {
+        self.loader = None;
+        self.show(None);
+    }
+
+    /// Show `path`, it is read in the background.
+    pub fn show(&mut self, path: Option<&Path>) {
+        if self.path.as_deref() == path { return; }
+        self.path = path.map(Path::to_path_buf);
+        self.offset_y = 0;
+        self.offset_x = 0;
+        self.cut = false;
+        self.content = match (&self.loader, path) {
+            (Some(loader), Some(path)) => {
+                let _ = loader.tx.send(path.to_path_buf());
+                Content::Loading
+            },
+            _ => Content::Empty("Nothing to preview"),
+        };
+    }
+
+    /// Take what was read in the background, call it regularly.
+    pub fn poll(&mut self) {
+        let loader = match &self.loader { Some(loader) => loader, None => return };
+        while let Ok((path, content, cut)) = loader.rx.try_recv() {
+            if self.path.as_ref() != Some(&path) { continue; }
+            self.content = content;
+            self.cut = cut;
+        }
+    }
+
+    fn rows(&self) -> usize {
+        match &self.content {
+            Content::Text(lines) => lines.len(),
+            Content::Hex(bytes)  => bytes.len().div_ceil(BYTES_PER_LINE),
+            Content::Dir(files)  => files.len(),
+            _                    => 1,
+        }
+    }
+
+    fn summary(&self) -> String {
+        let what = match &self.content {
+            Content::Text(lines) => format!("{} lines", lines.len()),
+            Content::Hex(bytes)  => format!("binary, {}", format_size(bytes.len() as u64)),
+            Content::Dir(files)  => format!("{} entries", files.len()),
+            _                    => String::new(),
+        };
+        if self.cut { format!("{} (cut)", what) } else { what }
+    }
+}
+
+impl Ui for PreviewPanel {
+    fn draw<B: Backend>(&mut self, f: &mut Frame<B>, rect: Rect, theme: &Theme) {
+        self.rect = rect;
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(1)])
+            .split(rect);
+        let r_text = chunks[0];
+        if r_text.area() == 0 { return; }
+
+        let (width, height) = (r_text.width as usize, r_text.height as usize);
+        self.offset_y = self.offset_y.min(self.rows().saturating_sub(1));
+        // only the visible part of a long line is worth wrapping
+        let keep = if self.wrap { width * height } else { self.offset_x + width + 1 };
+
+        let rows: Vec<Text> = match &self.content {
+            Content::Text(lines) => lines.iter().skip(self.offset_y).take(height)
+                .map(|line| match line.char_indices().nth(keep) {
+                    Some((end, _)) => &line[..end],
+                    None if line.is_empty() => " ", // an empty row would take no height
+                    None => line.as_str(),
+                })
+                .map(Text::raw)
+                .collect(),
+            Content::Hex(bytes) => bytes.chunks(BYTES_PER_LINE).enumerate().skip(self.offset_y).take(height)
+                .map(|(idx, chunk)| Text::from(hex_line(idx * BYTES_PER_LINE, chunk, theme)))
+                .collect(),
+            Content::Dir(files) => files.iter().skip(self.offset_y).take(height)
+                .map(|file| {
+                    let style = name_style(file.file_type, Indicator::of(file), &file.name, theme);
+                    Text::from(Spans::from(render_name(&file.name, &[], theme, style)))
+                })
+                .collect(),
+            Content::Loading    => vec![Text::styled("Loading...", theme.dim())],
+            Content::Empty(msg) => vec![Text::styled(*msg, theme.dim())],
+            Content::Error(e)   => vec![Text::styled(e.as_str(), theme.error())],
+        };
+
+        if self.wrap {
+            f.render_widget(List2::new(rows, 0, height).wrap(width), r_text);
+        } else {
+            // the end of a long line is marked
+            let rows = rows.into_iter().map(|row| wrap_text(row, self.offset_x + width, true, self.offset_x));
+            f.render_widget(List2::new(rows, 0, height), r_text);
+        }
+
+        let name = self.path.as_ref().and_then(|path| path.file_name()).map(|name| name.to_string_lossy()).unwrap_or_default();
+        f.render_widget(
+            Paragraph::new(Spans::from(vec![
+                Span::raw(" "),
+                Span::styled(name, theme.title()),
+                Span::raw(" "),
+                Span::styled(self.summary(), theme.dim()),
+            ])).alignment(Alignment::Left),
+            chunks[1],
+        );
+        f.render_widget(
+            Paragraph::new(Spans::from(vec![
+                Span::styled("Wrap", if self.wrap { theme.flag() } else { Style::default() }),
+                Span::raw(" "),
+            ])).alignment(Alignment::Right),
+            chunks[1],
+        );
+    }
+
+    fn on_event(&mut self, event: Event) {
+        let last = self.rows().saturating_sub(1);
+        let page = (self.rect.height as usize / 2).max(1);
+        match event {
+            Event::Action(Action::Up) | Event::ScrollUp => {
+                self.offset_y = self.offset_y.saturating_sub(1);
+            },
+            Event::Action(Action::Down) | Event::ScrollDown => {
+                self.offset_y = (self.offset_y + 1).min(last);
+            },
+            Event::Action(Action::PageUp) => {
+                self.offset_y = self.offset_y.saturating_sub(page);
+            },
+            Event::Action(Action::PageDown) => {
+                self.offset_y = (self.offset_y + page).min(last);
+            },
+            Event::Action(Action::Top) => { self.offset_y = 0; },
+            Event::Action(Action::Bottom) => {
+                self.offset_y = self.rows().saturating_sub(self.rect.height.saturating_sub(1) as usize);
+            },
+            Event::Action(Action::Left) => {
+                self.offset_x = self.offset_x.saturating_sub(STEP_X);
+            },
+            Event::Action(Action::Right) if !self.wrap => {
+                self.offset_x += STEP_X;
+            },
+            Event::Action(Action::Wrap) => {
+                self.wrap = !self.wrap;
+                self.offset_x = 0;
+            },
+            _ => { },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::ScratchDir;
+    use crate::fs::LocalHost;
+
+    fn lines(content: Content) -> Vec<String> {
+        match content {
+            Content::Text(lines) => lines,
+            _ => panic!("not text"),
+        }
+    }
+
+    fn is_hex(content: &Content) -> bool {
+        matches!(content, Content::Hex(_))
+    }
+
+    #[test]
+    fn text_or_binary() {
+        assert_eq!(lines(to_content(b"one\r\ntwo\n\nthree".to_vec(), false)), ["one", "two", "", "three"]);
+        assert_eq!(lines(to_content(vec![], false)), Vec::<String>::new());
+        assert!(is_hex(&to_content(b"ELF\0\x01".to_vec(), false)));
+        assert!(is_hex(&to_content(b"caf\xe9".to_vec(), false)));
+
+        // a character cut by the limit is dropped, only at the end and only when cut
+        let cut = "café".as_bytes()[..4].to_vec();
+        assert_eq!(lines(to_content(cut.clone(), true)), ["caf"]);
+        assert!(is_hex(&to_content(cut, false)));
+        assert!(is_hex(&to_content(b"caf\xe9 au lait".to_vec(), true)));
+    }
+
+    #[test]
+    fn printable_lines() {
+        assert_eq!(printable(""), "");
+        assert_eq!(printable("\tx"), "    x");
+        assert_eq!(printable("ab\tx\ty"), "ab  x   y");
+        // wide characters take two columns
+        assert_eq!(printable("中\tx"), "中  x");
+        assert_eq!(printable("a\x1b[31mb\x07"), "a\u{fffd}[31mb\u{fffd}");
+        assert_eq!(printable("dos\r"), "dos");
+    }
+
+    #[test]
+    fn load_cuts_at_the_limit() {
+        let dir = ScratchDir::new("preview", "load");
+        // the limit falls in the middle of the last `é`
+        let text = format!("a{}", "é".repeat(MAX_BYTES as usize / 2));
+        std::fs::write(dir.join("big"), &text).unwrap();
+        std::fs::write(dir.join("small"), "a\tb\n").unwrap();
+
+        let (content, cut) = load(&mut LocalHost, &dir.join("big")).unwrap();
+        assert!(cut);
+        assert_eq!(lines(content), [&text[..MAX_BYTES as usize - 1]]);
+        let (content, cut) = load(&mut LocalHost, &dir.join("small")).unwrap();
+        assert!(!cut);
+        assert_eq!(lines(content), ["a   b"]);
+    }
+}
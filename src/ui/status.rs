@@ -1,15 +1,48 @@
 use super::*;
+use super::info::{JobSummary, progress_bar, ratio, format_duration};
+use super::explorer::format_size;
 
-pub struct StatusPanel { }
+use std::time::Duration;
+
+pub struct StatusPanel {
+    jobs: Option<JobSummary>,
+}
 
 impl StatusPanel {
     pub fn new() -> Self {
-        Self {  }
+        Self { jobs: None }
+    }
+
+    pub fn set_jobs(&mut self, jobs: Option<JobSummary>) {
+        self.jobs = jobs;
     }
 }
 
 impl Ui for StatusPanel {
-    fn draw<B: Backend>(&mut self, f: &mut Frame<B>, rect: Rect, _theme: &Theme) {
+    fn draw<B: Backend>(&mut self, f: &mut Frame<B>, rect: Rect, theme: &Theme) {
+        const W_BAR: u16 = 20;
+        let mut rect = rect;
+
+        if let Some(jobs) = &self.jobs {
+            let eta = if jobs.rate > 0.0 {
+                format_duration(Duration::from_secs_f64(jobs.total.saturating_sub(jobs.done) as f64 / jobs.rate))
+            } else {
+                "--:--".to_string()
+            };
+            let text = format!(
+                " {:>3.0}% {}/s ETA {} ({} running, {} pending) ",
+                ratio(jobs.done, jobs.total) * 100.0, format_size(jobs.rate as u64), eta, jobs.running, jobs.pending,
+            );
+            let width = (text.len() as u16 + W_BAR).min(rect.width);
+            let r_jobs = Rect { x: rect.right() - width, width, .. rect };
+            rect.width -= width;
+
+            let r_bar = Rect { width: W_BAR.min(r_jobs.width), .. r_jobs };
+            let r_text = Rect { x: r_bar.right(), width: r_jobs.width - r_bar.width, .. r_jobs };
+            f.render_widget(progress_bar(ratio(jobs.done, jobs.total), theme), r_bar);
+            f.render_widget(Paragraph::new(text), r_text);
+        }
+
         f.render_widget(
             Paragraph::new("HELP: (?)help (q)exit")
                 .wrap(Wrap { trim: true })
@@ -18,4 +51,3 @@ impl Ui for StatusPanel {
         );
     }
 }
-
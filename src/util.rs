@@ -235,6 +235,162 @@ pub trait RectExt: Into<Rect> {
 
 impl<R: Into<Rect>> RectExt for R {  }
 
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+/// Shell style wildcards: `*`, `?`, `[abc]`, `[a-z]`, `[!abc]`, and `\` to escape.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None; // where to retry if the rest fails
+    while t < text.len() {
+        if pattern.get(p) == Some(&'*') {
+            star = Some((p, t));
+            p += 1;
+            continue;
+        }
+        if let Some(next) = glob_step(&pattern, p, text[t]) {
+            p = next;
+            t += 1;
+        } else if let Some((sp, st)) = star {
+            // let the star eat one more char
+            p = sp + 1;
+            t = st + 1;
+            star = Some((sp, st + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+// match `c` against the item at `pattern[p]`, and return where the next one starts
+fn glob_step(pattern: &[char], p: usize, c: char) -> Option<usize> {
+    match *pattern.get(p)? {
+        '?' => Some(p + 1),
+        '\\' if p + 1 < pattern.len() => if pattern[p + 1] == c { Some(p + 2) } else { None },
+        '[' => match glob_class(pattern, p, c) {
+            Some((true, next)) => Some(next),
+            Some((false, _)) => None,
+            None => if c == '[' { Some(p + 1) } else { None }, // no closing `]`, a plain char
+        },
+        x => if x == c { Some(p + 1) } else { None },
+    }
+}
+
+fn glob_class(pattern: &[char], p: usize, c: char) -> Option<(bool, usize)> {
+    let mut i = p + 1;
+    let negate = matches!(pattern.get(i), Some('!' | '^'));
+    if negate { i += 1; }
+
+    let mut matched = false;
+    let start = i;
+    loop {
+        let x = *pattern.get(i)?;
+        if x == ']' && i > start { break; }
+        if pattern.get(i + 1) == Some(&'-') && pattern.get(i + 2).is_some_and(|&y| y != ']') {
+            matched |= x <= c && c <= pattern[i + 2];
+            i += 3;
+        } else {
+            matched |= x == c;
+            i += 1;
+        }
+    }
+
+    Some((matched != negate, i + 1))
+}
+
+/// Match the chars of `pattern` in order anywhere in `text`, the case matters only if `pattern` has upper case.
+/// Gives a score, higher for matches that are tight, at word starts and in the last component of a path,
+/// with the byte ranges matched.
+pub fn fuzzy_match(pattern: &str, text: &str) -> Option<(i64, Vec<(usize, usize)>)> {
+    let smart = pattern.chars().any(char::is_uppercase);
+    let fold = |c: char| if smart { c } else { c.to_lowercase().next().unwrap_or(c) };
+    let pattern: Vec<char> = pattern.chars().map(fold).collect();
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    if pattern.is_empty() { return Some((0, vec![])); }
+
+    // the first match ending the soonest, then the shortest one ending there
+    let mut j = 0;
+    let mut end = None;
+    for (i, &(_, c)) in chars.iter().enumerate() {
+        if fold(c) == pattern[j] {
+            j += 1;
+            if j == pattern.len() { end = Some(i); break; }
+        }
+    }
+    let end = end?;
+    let mut positions = vec![0; pattern.len()];
+    let mut j = pattern.len();
+    for i in (0..=end).rev() {
+        if fold(chars[i].1) == pattern[j - 1] {
+            j -= 1;
+            positions[j] = i;
+            if j == 0 { break; }
+        }
+    }
+
+    let base = text.rfind('/').map_or(0, |idx| idx + 1);
+    let mut score = 0;
+    let mut ranges: Vec<(usize, usize)> = vec![];
+    for (k, &i) in positions.iter().enumerate() {
+        let (idx, c) = chars[i];
+        score += 16;
+        let prev = if i > 0 { Some(chars[i - 1].1) } else { None };
+        match prev {
+            None | Some('/')                        => { score += 12; },
+            Some('_' | '-' | '.' | ' ')             => { score += 10; },
+            Some(p) if p.is_lowercase() && c.is_uppercase() => { score += 8; },
+            _                                       => { },
+        }
+        if k > 0 {
+            let gap = (i - positions[k - 1] - 1) as i64;
+            score += if gap == 0 { 12 } else { -(gap.min(8)) };
+        }
+        if idx >= base { score += 4; }
+
+        match ranges.last_mut() {
+            Some(last) if last.1 == idx => { last.1 = idx + c.len_utf8(); },
+            _ => { ranges.push((idx, idx + c.len_utf8())); },
+        }
+    }
+    // the shorter of equal matches
+    score -= (chars.len() as i64) / 8;
+    Some((score, ranges))
+}
+
+/// Case-insensitive order with runs of digits compared as numbers, `file2` before `file10`.
+pub fn natural_cmp(a: &str, b: &str) -> std::cmp::Ordering {
+    use std::cmp::Ordering;
+    let (mut ia, mut ib) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        match (ia.peek().copied(), ib.peek().copied()) {
+            (None, None) => break,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(ca), Some(cb)) if ca.is_ascii_digit() && cb.is_ascii_digit() => {
+                let digits = |it: &mut std::iter::Peekable<std::str::Chars>| {
+                    let mut run = String::new();
+                    while let Some(c) = it.next_if(char::is_ascii_digit) { run.push(c); }
+                    run
+                };
+                let (da, db) = (digits(&mut ia), digits(&mut ib));
+                let (ta, tb) = (da.trim_start_matches('0'), db.trim_start_matches('0'));
+                let r = ta.len().cmp(&tb.len()).then_with(|| ta.cmp(tb));
+                if r != Ordering::Equal { return r; }
+            },
+            (Some(ca), Some(cb)) => {
+                let r = ca.to_lowercase().cmp(cb.to_lowercase());
+                if r != Ordering::Equal { return r; }
+                ia.next(); ib.next();
+            },
+        }
+    }
+    // equal but for the case or leading zeros, still a total order
+    a.cmp(b)
+}
+
 ////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
 #[derive(Debug, Clone)]
 pub struct LogItem {
@@ -283,3 +439,71 @@ impl TuiLog {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cmp::Ordering;
+
+    #[test]
+    fn natural_order() {
+        assert_eq!(natural_cmp("a9", "a10"), Ordering::Less);
+        assert_eq!(natural_cmp("a10", "a9"), Ordering::Greater);
+        assert_eq!(natural_cmp("file2.txt", "file10.txt"), Ordering::Less);
+        assert_eq!(natural_cmp("a", "B"), Ordering::Less);
+        // longer than any integer
+        assert_eq!(natural_cmp("v99999999999999999999999", "v100000000000000000000000"), Ordering::Less);
+        // equal but for leading zeros or the case, still never `Equal`
+        assert_eq!(natural_cmp("a01", "a1"), Ordering::Less);
+        assert_eq!(natural_cmp("A", "a"), Ordering::Less);
+        assert_eq!(natural_cmp("a1", "a1"), Ordering::Equal);
+
+        let mut names = vec!["a10", "b", "a9", "A1", "a", "a1b", "a1a"];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(names, ["a", "A1", "a1a", "a1b", "a9", "a10", "b"]);
+    }
+
+    #[test]
+    fn glob() {
+        assert!(glob_match("*.rs", "main.rs"));
+        assert!(!glob_match("*.rs", "main.rsx"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("", ""));
+        assert!(!glob_match("", "a"));
+        assert!(glob_match("a*b*c", "aXXbYbc"));
+        assert!(!glob_match("a*b*c", "aXXbY"));
+        assert!(glob_match("?.txt", "é.txt"));
+        assert!(!glob_match("?.txt", ".txt"));
+
+        assert!(glob_match("[a-c]x", "bx"));
+        assert!(!glob_match("[a-c]x", "dx"));
+        assert!(glob_match("[!a-c]x", "dx"));
+        assert!(!glob_match("[^a-c]x", "ax"));
+        assert!(glob_match("[]]", "]"));
+        assert!(glob_match("[a-]", "-"));
+        // no closing `]`, a plain `[`
+        assert!(glob_match("[ab", "[ab"));
+        assert!(glob_match(r"\*", "*"));
+        assert!(!glob_match(r"\*", "a"));
+    }
+
+    #[test]
+    fn fuzzy() {
+        assert_eq!(fuzzy_match("", "anything"), Some((0, vec![])));
+        assert_eq!(fuzzy_match("xyz", "foo_bar"), None);
+        assert_eq!(fuzzy_match("fb", "foo_bar").map(|m| m.1), Some(vec![(0, 1), (4, 5)]));
+        assert_eq!(fuzzy_match("oba", "foo_bar").map(|m| m.1), Some(vec![(2, 3), (4, 6)]));
+        // byte ranges of multi-byte chars
+        assert_eq!(fuzzy_match("fé", "café").map(|m| m.1), Some(vec![(2, 5)]));
+
+        // smart case
+        assert!(fuzzy_match("FB", "foo_bar").is_none());
+        assert!(fuzzy_match("FB", "FooBar").is_some());
+        assert!(fuzzy_match("fb", "FooBar").is_some());
+
+        let score = |pattern, text| fuzzy_match(pattern, text).unwrap().0;
+        assert!(score("ab", "ab") > score("ab", "axb"));
+        assert!(score("fb", "foo_bar") > score("fb", "xfxb"));
+        assert!(score("foo", "x/foo") > score("foo", "foo/x"));
+    }
+}
@@ -162,6 +162,8 @@ pub struct Slider {
     color: Color,
     direction: Direction,
     rev: bool,
+    // part of the area covered from its start instead of a scroll position, e.g. a progress
+    fill: Option<f64>,
 }
 
 impl Default for Slider {
@@ -173,6 +175,7 @@ impl Default for Slider {
             color: Color::Black,
             direction: Direction::Vertical,
             rev: false,
+            fill: None,
         }
     }
 }
@@ -182,6 +185,12 @@ impl Slider {
         Self { offset, total, .. Default::default() }
     }
 
+    /// Horizontal bar filled up to `ratio`, the last cell is filled by eighths.
+    pub fn filled(ratio: f64) -> Self {
+        let ratio = if ratio.is_finite() { ratio.clamp(0.0, 1.0) } else { 0.0 };
+        Self { fill: Some(ratio), direction: Direction::Horizontal, .. Default::default() }
+    }
+
     pub fn color(mut self, color: Color) -> Self {
         self.color = color;
         self
@@ -207,6 +216,10 @@ impl Slider {
             Direction::Vertical => area.height as f64,
             Direction::Horizontal => area.width as f64,
         };
+        if let Some(ratio) = self.fill {
+            let len = slider_win * ratio;
+            return if !self.rev { (0.0, len) } else { (slider_win - len, slider_win) };
+        }
         let offset = self.offset as f64;
         let total = self.total as f64;
 
@@ -265,7 +278,8 @@ impl Widget for Slider {
                         let mut bg = self.color;
                         let mut fg = self.slider_color;
 
-                        if x == start {
+                        // a start on a cell boundary takes the whole cell
+                        if x == start && rel_start % 1.0 > 0.0 {
                             symbol = get_unicode_block(rel_start % 1.0);
                             bg = self.slider_color;
                             fg = self.color;